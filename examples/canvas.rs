@@ -1,10 +1,10 @@
 extern crate howl;
 extern crate winapi;
-extern crate user32;
 extern crate gdi32;
 
 use howl::Frame;
 use howl::Canvas;
+use howl::Graphics;
 use howl::Application;
 use howl::Window;
 use howl::WindowEventHandler;
@@ -19,6 +19,8 @@ impl SimpleDrawingApp {
         let frame = Frame::new("Drawing Area", 400, 400);
         let c = Canvas::new(&frame, 10, 10, 400, 400);
 
+        c.set_double_buffered(true);
+
         frame.show();
 
         SimpleDrawingApp {
@@ -93,36 +95,8 @@ impl <'a> WindowEventHandler for DrawingInputHandler<'a> {
     fn on_left_mouse_down(&mut self, x: i32, y: i32) {
         println!("Drawing mouse input: {}, {}", x, y);
     }
-    fn on_event(&mut self, window: winapi::HWND, message : winapi::UINT,  w_param : winapi::WPARAM, l_param : winapi::LPARAM) -> bool {
-        //println!("on_event called for HWND: {} message: {:?}", window as i32, message);
-
-        match message {
-            winapi::WM_PAINT => {
-                let mut ps = winapi::PAINTSTRUCT {
-                    hdc: 0 as winapi::HDC,
-                    fErase: 0,
-                    rcPaint: winapi::RECT {
-                        top: 0,
-                        left: 0,
-                        right: 0,
-                        bottom: 0
-                    },
-                    fRestore: 0,
-                    fIncUpdate: 0,
-                    rgbReserved: [0; 32]
-                };
-                unsafe {
-                    user32::BeginPaint(self.app.canvas.get_hwnd(), &mut ps);
-    			    self.app.draw_canvas(ps.hdc);
-    			    user32::EndPaint(self.app.canvas.get_hwnd(), &mut ps);
-                }
-			},
-            _ => {
-                return self.dispatch_event(window, message,  w_param, l_param);
-            }
-        }
-
-        return true;
+    fn on_paint(&mut self, graphics: &Graphics, dirty: &winapi::RECT) {
+        self.app.draw_canvas(graphics.get_dc());
     }
 }
 
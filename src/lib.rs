@@ -2,600 +2,6485 @@
 
 extern crate kernel32;
 extern crate user32;
+extern crate gdi32;
+extern crate comctl32;
 extern crate winapi;
 extern crate libc;
+extern crate image;
+extern crate regex;
+extern crate regex_automata;
 
 use std::ptr;
 use std::ffi::OsStr;
 use std::os::windows::ffi::OsStrExt;
 use std::mem;
 use std::raw;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::sync::{mpsc, Arc, Once};
+use std::rc::Rc;
+use std::pin::Pin;
+use std::future::Future;
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Duration;
+use std::marker::PhantomData;
+use std::cmp::Ordering;
+
+//Posted to the invoke window. l_param carries a boxed closure.
+const WM_HOWL_INVOKE: winapi::UINT = winapi::WM_APP + 1;
+//Posted to the invoke window. w_param is the id of the task to poll.
+const WM_HOWL_WAKE: winapi::UINT = winapi::WM_APP + 2;
+
+#[link(name = "gdi32")]
+extern "system" {
+    //Same as AlphaBlend() from msimg32 but exported by gdi32
+    fn GdiAlphaBlend(dest: winapi::HDC, dest_x: i32, dest_y: i32, dest_width: i32, dest_height: i32,
+        src: winapi::HDC, src_x: i32, src_y: i32, src_width: i32, src_height: i32,
+        blend: winapi::BLENDFUNCTION) -> winapi::BOOL;
+}
 
-pub fn to_wchar(str : &str) -> Vec<u16> {
-    OsStr::new(str).encode_wide().chain(Some(0).into_iter()).collect()
+thread_local! {
+    //Hidden message-only window used to marshal work onto this thread
+    static INVOKE_WINDOW: Cell<winapi::HWND> = Cell::new(ptr::null_mut());
+    static EXECUTOR: RefCell<Executor> = RefCell::new(Executor {
+        tasks: Vec::new(),
+        free: Vec::new()
+    });
+    static TIMERS: RefCell<HashMap<usize, Rc<TimerEntry>>> = RefCell::new(HashMap::new());
+    //Starts high to stay clear of ids passed to Window::set_timer()
+    static NEXT_TIMER_ID: Cell<usize> = Cell::new(0x4000_0000);
+    //Current virtual time when a VirtualClock is installed
+    static VIRTUAL_NOW: Cell<Option<Duration>> = Cell::new(None);
+    static IDLE_HANDLERS: RefCell<Vec<Box<FnMut() -> bool>>> = RefCell::new(Vec::new());
+    //Solid brushes are shared by all windows and live as long as the thread
+    static BRUSHES: RefCell<HashMap<winapi::COLORREF, winapi::HBRUSH>> = RefCell::new(HashMap::new());
+    //Dynamic tooltips keyed by the window they belong to
+    static TOOLTIP_CALLBACKS: RefCell<HashMap<usize, Rc<TooltipCallback>>> = RefCell::new(HashMap::new());
+    //Text handed to the tooltip control. It must stay alive after the
+    //notification returns.
+    static TOOLTIP_TEXT: RefCell<Vec<u16>> = RefCell::new(vec![0]);
+    //Validation of subclassed Edit controls keyed by their window
+    static VALIDATIONS: RefCell<HashMap<usize, Rc<EditValidation>>> = RefCell::new(HashMap::new());
 }
 
-unsafe extern "system" fn wnd_proc(
-    window: winapi::HWND,
-    message: winapi::UINT,
-    w_param: winapi::WPARAM,
-    l_param: winapi::LPARAM) -> winapi::LRESULT {
+fn get_prop(window: winapi::HWND, name: &str) -> winapi::HANDLE {
+    let prop = to_wchar(name);
 
-	//println!("wnd_proc called for HWND: {} message: {:?}", window as i32, message);
+    unsafe {
+        user32::GetPropW(window, prop.as_ptr())
+    }
+}
 
-    if let Some(handler) = window.get_event_handler() {
-        if handler.on_event(window, message, w_param, l_param) {
-		    return 0;
-	    }
+fn set_prop(window: winapi::HWND, name: &str, value: winapi::HANDLE) {
+    let prop = to_wchar(name);
+
+    unsafe {
+        if value.is_null() {
+            user32::RemovePropW(window, prop.as_ptr());
+        } else {
+            user32::SetPropW(window, prop.as_ptr(), value);
+        }
     }
+}
 
-    return user32::DefWindowProcW(window, message, w_param, l_param);
+fn is_double_buffered(window: winapi::HWND) -> bool {
+    !get_prop(window, "cwnd.doublebuffer").is_null()
 }
 
-unsafe extern "system" fn timer_proc(
-    window: winapi::HWND,
-    message: winapi::UINT,
-    id: winapi::UINT_PTR,
-    not_used: winapi::DWORD) {
+//Colors are stored in window properties with a marker bit so that black
+//can be told apart from no color.
+fn set_color_prop(window: winapi::HWND, name: &str, color: Color) {
+    set_prop(window, name, (color.to_colorref() | 0x0100_0000) as usize as winapi::HANDLE);
+}
 
-	//println!("timer_proc called for HWND: {}", window as i32);
+fn get_color_prop(window: winapi::HWND, name: &str) -> Option<winapi::COLORREF> {
+    let value = get_prop(window, name) as usize;
 
-    if let Some(handler) = window.get_event_handler() {
-        handler.on_timer(window, id as usize);
+    if value == 0 {
+        None
+    } else {
+        Some((value & 0x00FF_FFFF) as winapi::COLORREF)
     }
 }
 
-pub struct Application;
-static mut continue_loop: bool = false;
+fn get_brush(color: winapi::COLORREF) -> winapi::HBRUSH {
+    BRUSHES.with(|brushes| {
+        *brushes.borrow_mut().entry(color).or_insert_with(|| unsafe {
+            gdi32::CreateSolidBrush(color)
+        })
+    })
+}
 
-impl Application {
-    pub fn init() {
-        let class_name = "HOWL";
+fn erase_background(window: winapi::HWND, dc: winapi::HDC) {
+    unsafe {
+        match get_color_prop(window, "cwnd.bgcolor") {
+            Some(color) => {
+                let mut client = winapi::RECT {
+                    top: 0, left: 0, right: 0, bottom: 0
+                };
 
-        Application::register_class(class_name, Some(wnd_proc));
+                user32::GetClientRect(window, &mut client);
+                user32::FillRect(dc, &client, get_brush(color));
+            },
+            None => {
+                user32::DefWindowProcW(window, winapi::WM_ERASEBKGND, dc as winapi::WPARAM, 0);
+            }
+        }
     }
+}
 
-    pub fn get_instance() -> winapi::HINSTANCE {
-        unsafe {
-            let instance = kernel32::GetModuleHandleW(ptr::null());
-            if !instance.is_null() {
-                return instance;
-            }
+//Handles WM_CTLCOLOR* sent by a child control. Returns the background brush
+//or None to let the default colors apply.
+fn control_color(parent: winapi::HWND, message: winapi::UINT, dc: winapi::HDC, control: winapi::HWND) -> Option<winapi::HBRUSH> {
+    let WM_CTLCOLOREDIT = 0x0133;
+    let WM_CTLCOLORLISTBOX = 0x0134;
+    let COLOR_BTNFACE = 15;
+    let TRANSPARENT = 1;
+
+    let text_color = get_color_prop(control, "cwnd.fgcolor");
+    let mut background = get_color_prop(control, "cwnd.bgcolor");
+    let input = message == WM_CTLCOLOREDIT || message == WM_CTLCOLORLISTBOX;
+
+    //Labels and buttons blend with the parent unless given their own color
+    if background.is_none() && !input {
+        background = get_color_prop(parent, "cwnd.bgcolor");
+    }
 
-            panic!("GetModuleHandleW error: {}", kernel32::GetLastError());
-        }
+    //An Edit failing validation is tinted red
+    if !get_prop(control, "cwnd.invalid").is_null() {
+        background = Some(INVALID_BACKGROUND);
     }
 
-    pub fn register_class(class_name : &str, wnd_proc: winapi::WNDPROC) {
-        let class = winapi::WNDCLASSW {
-            style: winapi::CS_HREDRAW | winapi::CS_VREDRAW | winapi::CS_DBLCLKS,
-            lpfnWndProc: wnd_proc,
-            cbClsExtra: 0,
-            cbWndExtra: 0,
-            hInstance: Application::get_instance(),
-            hIcon: ptr::null_mut(),
-            hCursor: ptr::null_mut(),
-            hbrBackground: winapi::COLOR_WINDOW as winapi::HBRUSH,
-            lpszMenuName: ptr::null_mut(),
-            lpszClassName: to_wchar(class_name).as_ptr()
+    if text_color.is_none() && background.is_none() {
+        return None;
+    }
+
+    unsafe {
+        let background = match background {
+            Some(color) => color,
+            None => user32::GetSysColor(if input {winapi::COLOR_WINDOW} else {COLOR_BTNFACE})
         };
-        unsafe {
-            let atom = user32::RegisterClassW(&class);
 
-            if atom == 0 {
-                if  kernel32::GetLastError() != winapi::ERROR_CLASS_ALREADY_EXISTS {
-                    panic!("RegisterClassW error: {}", kernel32::GetLastError());
-                }
-            }
+        if let Some(color) = text_color {
+            gdi32::SetTextColor(dc, color);
+        }
+
+        gdi32::SetBkColor(dc, background);
+
+        if !input {
+            gdi32::SetBkMode(dc, TRANSPARENT);
         }
+
+        Some(get_brush(background))
     }
+}
 
-    pub fn main_loop() {
-        let mut message = winapi::MSG {
-            hwnd: ptr::null_mut(),
-            message: 0,
-            wParam: 0,
-            lParam: 0,
-            time: 0,
-            pt: winapi::POINT {
-                x: 0,
-                y: 0
-            }
-        };
+//Owner draws a row of a VirtualList. Returns false if the control is not
+//a VirtualList.
+fn draw_virtual_item(item: &winapi::DRAWITEMSTRUCT) -> bool {
+    let model = get_prop(item.hwndItem, "cwnd.model") as *const Rc<ListModel>;
+    let ODS_SELECTED = 0x0001;
+    let ODS_FOCUS = 0x0010;
+    let COLOR_HIGHLIGHT = 13;
+    let COLOR_HIGHLIGHTTEXT = 14;
+    let TRANSPARENT = 1;
+
+    if model.is_null() {
+        return false;
+    }
 
-        unsafe {
-            continue_loop = true;
+    //Focus rectangle of an empty list
+    if item.itemID == !0 {
+        return true;
+    }
 
-            while continue_loop {
-                    let status = user32::GetMessageW(&mut message, ptr::null_mut(), 0, 0);
-                    if status == 0 {
-                        break;
-                    }
+    unsafe {
+        let model = &*model;
 
-                    user32::TranslateMessage(&message);
-                    user32::DispatchMessageW(&message);
-            }
+        if item.itemID as usize >= model.len() {
+            return true;
         }
-    }
 
-    pub fn exit_loop() {
-        unsafe {
-            continue_loop = false;
+        let selected = item.itemState & ODS_SELECTED != 0;
+        let (background, text_color) = if selected {
+            (user32::GetSysColor(COLOR_HIGHLIGHT), user32::GetSysColor(COLOR_HIGHLIGHTTEXT))
+        } else {
+            (get_color_prop(item.hwndItem, "cwnd.bgcolor").unwrap_or(user32::GetSysColor(winapi::COLOR_WINDOW)),
+                get_color_prop(item.hwndItem, "cwnd.fgcolor").unwrap_or(user32::GetSysColor(winapi::COLOR_WINDOWTEXT)))
+        };
+
+        user32::FillRect(item.hDC, &item.rcItem, get_brush(background));
+
+        let mut rect = item.rcItem;
+        rect.left = rect.left + 2;
+
+        let text: Vec<u16> = OsStr::new(&model.item(item.itemID as usize)).encode_wide().collect();
+
+        gdi32::SetTextColor(item.hDC, text_color);
+        gdi32::SetBkMode(item.hDC, TRANSPARENT);
+        user32::DrawTextW(item.hDC, text.as_ptr(), text.len() as i32, &mut rect, TextFormat::new().flags());
+
+        if item.itemState & ODS_FOCUS != 0 {
+            user32::DrawFocusRect(item.hDC, &item.rcItem);
         }
     }
-}
 
-pub struct WindowBuilder<'a> {
-	x: i32,
-	y: i32,
-	width: i32,
-	height: i32,
-	style: winapi::DWORD,
-	extra_style: winapi::DWORD,
-	class_name: &'a str,
-	title: &'a str,
-	parent: winapi::HWND,
-	id: u16
+    true
 }
 
-impl <'a> WindowBuilder<'a> {
-	pub fn new() -> WindowBuilder<'a> {
-		WindowBuilder {
-			x: winapi::CW_USEDEFAULT,
-			y: winapi::CW_USEDEFAULT,
-			width: winapi::CW_USEDEFAULT,
-			height: winapi::CW_USEDEFAULT,
-			style: 0,
-			extra_style: 0,
-			class_name: "",
-			title: "",
-			parent: 0 as winapi::HWND,
-			id: 0
-		}
-	}
-	pub fn style(&'a mut self, style: winapi::DWORD) -> &mut WindowBuilder {
-		self.style = style;
+//Re-reads the row count of a VirtualList keeping the selection and scroll
+//position where possible.
+fn refresh_virtual_list(window: winapi::HWND) {
+    let model = get_prop(window, "cwnd.model") as *const Rc<ListModel>;
+    let LB_SETCURSEL = 390;
+    let LB_GETCURSEL = 392;
+    let LB_GETTOPINDEX = 398;
+    let LB_SETTOPINDEX = 407;
+    let LB_SETCOUNT = 423;
+
+    if model.is_null() {
+        return;
+    }
 
-		self
-	}
-	pub fn extra_style(&'a mut self, extra_style: winapi::DWORD) -> &mut WindowBuilder {
-		self.extra_style = extra_style;
+    unsafe {
+        let len = (*model).len();
+        let sel = user32::SendMessageW(window, LB_GETCURSEL, 0, 0);
+        let top = user32::SendMessageW(window, LB_GETTOPINDEX, 0, 0);
 
-		self
-	}
-	pub fn parent(&'a mut self, parent: winapi::HWND) -> &mut WindowBuilder {
-		self.parent = parent;
+        user32::SendMessageW(window, LB_SETCOUNT, len as winapi::WPARAM, 0);
 
-		self
-	}
-	pub fn position(&'a mut self, x: i32, y: i32) -> &mut WindowBuilder {
-		self.x = x;
-		self.y = y;
+        if sel >= 0 && (sel as usize) < len {
+            user32::SendMessageW(window, LB_SETCURSEL, sel as winapi::WPARAM, 0);
+        }
 
-		self
-	}
-	pub fn size(&'a mut self, width: i32, height: i32) -> &mut WindowBuilder {
-		self.width = width;
-		self.height = height;
+        if top > 0 && (top as usize) < len {
+            user32::SendMessageW(window, LB_SETTOPINDEX, top as winapi::WPARAM, 0);
+        }
 
-		self
-	}
+        user32::InvalidateRect(window, ptr::null(), 1);
+    }
+}
 
-	pub fn id(&'a mut self, id: u16) -> &mut WindowBuilder {
-		self.id = id;
+//Builds an image list from images of the same size. Images of another
+//size are scaled to the size of the first one.
+fn create_image_list(images: &[&Image]) -> winapi::HIMAGELIST {
+    let ILC_COLOR32 = 0x0020;
 
-		self
-	}
+    if images.is_empty() {
+        return ptr::null_mut();
+    }
 
-	pub fn class_name(&'a mut self, class_name: &'a str) -> &mut WindowBuilder {
-		self.class_name = class_name;
+    let width = images[0].width();
+    let height = images[0].height();
 
-		self
-	}
-	pub fn title(&'a mut self, title: &'a str) -> &mut WindowBuilder {
-		self.title = title;
+    unsafe {
+        let list = comctl32::ImageList_Create(width, height, ILC_COLOR32, images.len() as i32, 0);
 
-		self
-	}
+        for image in images {
+            if image.width() == width && image.height() == height {
+                comctl32::ImageList_Add(list, image.get_bitmap(), ptr::null_mut());
+            } else {
+                if let Ok(scaled) = image.scale(width, height) {
+                    comctl32::ImageList_Add(list, scaled.get_bitmap(), ptr::null_mut());
+                }
+            }
+        }
 
-	pub fn button(&'a mut self, title: &'a str) -> &mut WindowBuilder {
-		self.title(title)
-		.class_name("BUTTON")
-		.style(winapi::WS_VISIBLE | winapi::WS_TABSTOP | winapi::WS_CHILD | winapi::BS_PUSHBUTTON)
-	}
+        list
+    }
+}
 
-	pub fn checkbox(&'a mut self, title: &'a str) -> &mut WindowBuilder {
-		self.title(title)
-		.class_name("BUTTON")
-		.style(winapi::WS_VISIBLE | winapi::WS_TABSTOP | winapi::WS_CHILD | winapi::BS_CHECKBOX)
-	}
+//Returns the page window stored with a tab.
+fn get_tab_page(tab: winapi::HWND, index: i32) -> winapi::HWND {
+    let TCM_GETITEMW = 0x133C;
+    let TCIF_PARAM = 0x0008;
 
-	pub fn frame(&'a mut self, title: &'a str) -> &mut WindowBuilder {
-		self.title(title)
-		.class_name("HOWL")
-		.style(winapi::WS_THICKFRAME | winapi::WS_MINIMIZEBOX | winapi::WS_MAXIMIZEBOX | winapi::WS_SYSMENU)
-		.extra_style(winapi::WS_EX_CLIENTEDGE)
-	}
+    unsafe {
+        let mut item: winapi::TCITEMW = mem::zeroed();
 
-	pub fn create(&self) -> winapi::HWND {
-		unsafe {
-		    let window =  user32::CreateWindowExW(
-		        self.extra_style,
-		        to_wchar(self.class_name).as_ptr(),
-		        to_wchar(self.title).as_ptr(),
-		        self.style,
-		        self.x,
-		        self.y,
-		        self.width,
-		        self.height,
-		        self.parent,
-		        self.id as winapi::HMENU,
-		        Application::get_instance(),
-		        ptr::null_mut());
+        item.mask = TCIF_PARAM;
 
-		    if window.is_null() {
-		        panic!("CreateWindowExW error: {}", kernel32::GetLastError());
-		    }
+        if user32::SendMessageW(tab, TCM_GETITEMW, index as winapi::WPARAM, &mut item as *mut winapi::TCITEMW as winapi::LPARAM) == 0 {
+            return ptr::null_mut();
+        }
 
-		    window
-		}
-	}
+        item.lParam as winapi::HWND
+    }
 }
 
-pub trait Window {
-	fn get_hwnd(&self) -> winapi::HWND;
+//Fits the pages of a TabControl into its display area and shows only the
+//page of the selected tab.
+fn layout_tab_pages(tab: winapi::HWND) {
+    let TCM_GETITEMCOUNT = 0x1304;
+    let TCM_GETCURSEL = 0x130B;
+    let TCM_ADJUSTRECT = 0x1328;
+
+    unsafe {
+        let count = user32::SendMessageW(tab, TCM_GETITEMCOUNT, 0, 0) as i32;
+        let selected = user32::SendMessageW(tab, TCM_GETCURSEL, 0, 0) as i32;
+        let mut area = winapi::RECT {
+            top: 0, left: 0, right: 0, bottom: 0
+        };
 
-	fn show(&self) {
-		unsafe {
-			user32::ShowWindow(self.get_hwnd(), 5);
-		}
-	}
+        user32::GetClientRect(tab, &mut area);
+        user32::SendMessageW(tab, TCM_ADJUSTRECT, 0, &mut area as *mut winapi::RECT as winapi::LPARAM);
 
-	fn hide(&self) {
-		unsafe {
-			user32::ShowWindow(self.get_hwnd(), 0);
-		}
-	}
+        for index in 0..count {
+            let page = get_tab_page(tab, index);
 
-    fn resize(&self, x : i32, y : i32, width : i32, height : i32) {
-        unsafe {
-            user32::MoveWindow(self.get_hwnd(), x, y, width, height, 1);
+            if page.is_null() {
+                continue;
+            }
+
+            user32::MoveWindow(page, area.left, area.top, area.right - area.left, area.bottom - area.top, 1);
+            user32::ShowWindow(page, if index == selected {5} else {0});
         }
     }
+}
 
-    fn size(&self, width: &mut i32, height: &mut i32) {
-        unsafe {
-            let mut rect = winapi::RECT {
-                    top: 0, left: 0, right: 0, bottom: 0
-            };
-            user32::GetWindowRect(self.get_hwnd(), &mut rect);
-            println!("size(): {:?}", rect);
-            *width = rect.right - rect.left;
-            *height = rect.bottom - rect.top;
-        }
+fn get_class_name(window: winapi::HWND) -> String {
+    let mut v : Vec<u16> = vec![0; 256];
+
+    unsafe {
+        let len = user32::GetClassNameW(window, v.as_mut_ptr(), v.len() as i32);
+
+        v.truncate(if len > 0 {len as usize} else {0});
     }
 
-    fn get_position(&self) -> winapi::RECT {
-        unsafe {
-            let mut rect = winapi::RECT {
-                    top: 0, left: 0, right: 0, bottom: 0
-            };
-            user32::GetWindowRect(self.get_hwnd(), &mut rect);
-            println!("{:?}", rect);
+    return String::from_utf16_lossy(&v[..]);
+}
+
+//Lets an attached ToolBar and StatusBar fit themselves to a new parent size.
+fn layout_bars(window: winapi::HWND) {
+    let TB_AUTOSIZE = 0x0421;
+    let toolbar = get_prop(window, "cwnd.toolbar") as winapi::HWND;
+    let status_bar = get_prop(window, "cwnd.statusbar") as winapi::HWND;
+
+    unsafe {
+        if !toolbar.is_null() {
+            user32::SendMessageW(toolbar, TB_AUTOSIZE, 0, 0);
+        }
+
+        if !status_bar.is_null() {
+            user32::SendMessageW(status_bar, winapi::WM_SIZE, 0, 0);
+        }
+    }
+}
+
+//Height taken up by a visible bar, or 0.
+fn bar_height(bar: winapi::HWND) -> i32 {
+    if bar.is_null() {
+        return 0;
+    }
+
+    unsafe {
+        //The bar's own flag. IsWindowVisible() is false until the parent is shown.
+        if user32::GetWindowLongW(bar, winapi::GWL_STYLE) as winapi::DWORD & winapi::WS_VISIBLE == 0 {
+            return 0;
+        }
+
+        let mut rect = winapi::RECT {
+            top: 0, left: 0, right: 0, bottom: 0
+        };
+
+        user32::GetWindowRect(bar, &mut rect);
+
+        rect.bottom - rect.top
+    }
+}
+
+struct TooltipCallback {
+    callback: Box<Fn(i32, i32) -> Option<String>>,
+    last_text: RefCell<Option<String>>
+}
+
+//Returns the tooltip control shared by all windows under the same top
+//level window, creating it if needed.
+fn get_tooltip_control(window: winapi::HWND) -> winapi::HWND {
+    let GA_ROOT = 2;
+    let TTS_ALWAYSTIP = 0x01;
+    let TTS_NOPREFIX = 0x02;
+    let TTM_SETMAXTIPWIDTH = 0x0418;
+
+    unsafe {
+        let root = user32::GetAncestor(window, GA_ROOT);
+        let tooltip = get_prop(root, "cwnd.tooltip") as winapi::HWND;
+
+        if !tooltip.is_null() {
+            return tooltip;
+        }
+
+        let tooltip = WindowBuilder::new()
+            .class_name("tooltips_class32")
+            .style(winapi::WS_POPUP | TTS_ALWAYSTIP | TTS_NOPREFIX)
+            .extra_style(winapi::WS_EX_TOPMOST)
+            .parent(root)
+            .create();
+
+        //Allows multi-line tips
+        user32::SendMessageW(tooltip, TTM_SETMAXTIPWIDTH, 0, 400);
+        set_prop(root, "cwnd.tooltip", tooltip as winapi::HANDLE);
+
+        tooltip
+    }
+}
+
+fn new_tool_info(window: winapi::HWND) -> winapi::TOOLINFOW {
+    let TTF_IDISHWND = 0x0001;
+    let TTF_SUBCLASS = 0x0010;
+    let GA_ROOT = 2;
+
+    unsafe {
+        let mut info: winapi::TOOLINFOW = mem::zeroed();
+
+        info.cbSize = mem::size_of::<winapi::TOOLINFOW>() as winapi::UINT;
+        info.uFlags = TTF_IDISHWND | TTF_SUBCLASS;
+        //Notifications go to the top level window, which is a howl window
+        info.hwnd = user32::GetAncestor(window, GA_ROOT);
+        info.uId = window as winapi::UINT_PTR;
+
+        info
+    }
+}
+
+fn add_tool(window: winapi::HWND, text: *const u16) {
+    let TTM_ADDTOOLW = 0x0432;
+    let TTM_DELTOOLW = 0x0433;
+    let tooltip = get_tooltip_control(window);
+    let mut info = new_tool_info(window);
+
+    info.lpszText = text as *mut u16;
+
+    unsafe {
+        user32::SendMessageW(tooltip, TTM_DELTOOLW, 0, &info as *const winapi::TOOLINFOW as winapi::LPARAM);
+        user32::SendMessageW(tooltip, TTM_ADDTOOLW, 0, &info as *const winapi::TOOLINFOW as winapi::LPARAM);
+    }
+}
+
+//Answers TTN_GETDISPINFOW for a tool with a tooltip callback. Returns false
+//for other tools.
+fn get_tooltip_text(info: &mut winapi::NMTTDISPINFOW) -> bool {
+    let tool = info.hdr.idFrom as winapi::HWND;
+    let callback = TOOLTIP_CALLBACKS.with(|callbacks| callbacks.borrow().get(&(tool as usize)).cloned());
+
+    let callback = match callback {
+        Some(callback) => callback,
+        None => return false
+    };
+
+    let mut point = winapi::POINT {
+        x: 0,
+        y: 0
+    };
+
+    unsafe {
+        user32::GetCursorPos(&mut point);
+        user32::ScreenToClient(tool, &mut point);
+    }
+
+    let text = (callback.callback)(point.x, point.y);
+
+    TOOLTIP_TEXT.with(|buffer| {
+        let mut buffer = buffer.borrow_mut();
+
+        //An empty text hides the tip
+        *buffer = to_wchar(text.as_ref().map(|t| t.as_str()).unwrap_or(""));
+        info.lpszText = buffer.as_mut_ptr();
+    });
+
+    *callback.last_text.borrow_mut() = text;
+
+    true
+}
+
+//Makes the tooltip ask for new text when the mouse moves to a spot that
+//has a different tip.
+fn track_tooltip(window: winapi::HWND, x: i32, y: i32) {
+    let TTM_UPDATE = 0x041D;
+    let callback = TOOLTIP_CALLBACKS.with(|callbacks| callbacks.borrow().get(&(window as usize)).cloned());
+
+    if let Some(callback) = callback {
+        let text = (callback.callback)(x, y);
+
+        if *callback.last_text.borrow() != text {
+            *callback.last_text.borrow_mut() = text;
+
+            unsafe {
+                user32::SendMessageW(get_tooltip_control(window), TTM_UPDATE, 0, 0);
+            }
+        }
+    }
+}
+
+//Passes notifications from child controls of a container on to the window
+//that owns the container. Returns false for other messages.
+fn forward_notification(window: winapi::HWND, message: winapi::UINT, w_param: winapi::WPARAM, l_param: winapi::LPARAM) -> bool {
+    let is_control_scroll = (message == winapi::WM_HSCROLL || message == winapi::WM_VSCROLL) && l_param != 0;
+
+    if message != winapi::WM_COMMAND && message != winapi::WM_NOTIFY && !is_control_scroll {
+        return false;
+    }
+
+    unsafe {
+        user32::SendMessageW(user32::GetParent(window), message, w_param, l_param);
+    }
+
+    true
+}
+
+fn new_scroll_info(mask: winapi::UINT) -> winapi::SCROLLINFO {
+    winapi::SCROLLINFO {
+        cbSize: mem::size_of::<winapi::SCROLLINFO>() as winapi::UINT,
+        fMask: mask,
+        nMin: 0,
+        nMax: 0,
+        nPage: 0,
+        nPos: 0,
+        nTrackPos: 0
+    }
+}
+
+fn invoke_window() -> winapi::HWND {
+    let window = INVOKE_WINDOW.with(|invoke_window| invoke_window.get());
+
+    if window.is_null() {
+        panic!("Application::init() was not called from this thread.");
+    }
+
+    window
+}
+
+//Queues a closure on the invoke window. Sending across threads is
+//checked by the callers.
+fn post_invoke(window: winapi::HWND, callback: Box<FnOnce()>) {
+    let callback: Box<Box<FnOnce()>> = Box::new(callback);
+    let callback = Box::into_raw(callback);
+
+    unsafe {
+        if user32::PostMessageW(window, WM_HOWL_INVOKE, 0, callback as winapi::LPARAM) == 0 {
+            //The window is gone. Reclaim the closure.
+            drop(Box::from_raw(callback));
+
+            println!("PostMessageW error: {}", kernel32::GetLastError());
+        }
+    }
+}
+
+pub fn to_wchar(str : &str) -> Vec<u16> {
+    OsStr::new(str).encode_wide().chain(Some(0).into_iter()).collect()
+}
+
+unsafe extern "system" fn wnd_proc(
+    window: winapi::HWND,
+    message: winapi::UINT,
+    w_param: winapi::WPARAM,
+    l_param: winapi::LPARAM) -> winapi::LRESULT {
+
+	//println!("wnd_proc called for HWND: {} message: {:?}", window as i32, message);
+
+    if message == WM_HOWL_INVOKE {
+        let callback: Box<Box<FnOnce()>> = Box::from_raw(l_param as *mut Box<FnOnce()>);
+
+        (*callback)();
+
+        return 0;
+    }
+
+    if message >= winapi::WM_CTLCOLORMSGBOX && message <= winapi::WM_CTLCOLORSTATIC {
+        if let Some(brush) = control_color(window, message, w_param as winapi::HDC, l_param as winapi::HWND) {
+            return brush as winapi::LRESULT;
+        }
+    }
+
+    if message == winapi::WM_ERASEBKGND && !is_double_buffered(window) &&
+        get_color_prop(window, "cwnd.bgcolor").is_some() {
+
+        erase_background(window, w_param as winapi::HDC);
+
+        return 1;
+    }
+
+    if message == winapi::WM_DRAWITEM {
+        if draw_virtual_item(&*(l_param as *const winapi::DRAWITEMSTRUCT)) {
+            return 1;
+        }
+    }
+
+    if message == winapi::WM_MOUSEMOVE {
+        track_tooltip(window, winapi::GET_X_LPARAM(l_param), winapi::GET_Y_LPARAM(l_param));
+    }
+
+    if message == winapi::WM_NCDESTROY {
+        let PM_REMOVE = 0x0001;
+        let mut pending: winapi::MSG = mem::zeroed();
+
+        //Free closures that will never run, which lets send() return None
+        while user32::PeekMessageW(&mut pending, window, WM_HOWL_INVOKE, WM_HOWL_INVOKE, PM_REMOVE) != 0 {
+            drop(Box::from_raw(pending.lParam as *mut Box<FnOnce()>));
+        }
+
+        TOOLTIP_CALLBACKS.with(|callbacks| callbacks.borrow_mut().remove(&(window as usize)));
+
+        //Child windows, and so the ToolBar, are already destroyed
+        let toolbar_images = get_prop(window, "cwnd.toolbarimages") as winapi::HIMAGELIST;
+
+        if !toolbar_images.is_null() {
+            set_prop(window, "cwnd.toolbarimages", ptr::null_mut());
+            comctl32::ImageList_Destroy(toolbar_images);
+        }
+    }
+
+    if message == winapi::WM_NOTIFY {
+        let TCN_SELCHANGE = -551i32 as winapi::UINT;
+        let TTN_GETDISPINFOW = -530i32 as winapi::UINT;
+        let header = &*(l_param as *const winapi::NMHDR);
+
+        if header.code == TTN_GETDISPINFOW && get_tooltip_text(&mut *(l_param as *mut winapi::NMTTDISPINFOW)) {
+            return 0;
+        }
+
+        //Switch pages even if no event handler is attached
+        if header.code == TCN_SELCHANGE {
+            layout_tab_pages(header.hwndFrom);
+        }
+    }
+
+    if message == winapi::WM_SIZE {
+        layout_bars(window);
+    }
+
+    if message == WM_HOWL_WAKE {
+        poll_task(window, w_param as usize);
+
+        return 0;
+    }
+
+    if let Some(handler) = window.get_event_handler() {
+        if handler.on_event(window, message, w_param, l_param) {
+            //Nonzero tells Windows the background was taken care of
+            if message == winapi::WM_ERASEBKGND {
+                return 1;
+            }
+
+		    return 0;
+	    }
+    }
+
+    return user32::DefWindowProcW(window, message, w_param, l_param);
+}
+
+unsafe extern "system" fn timer_proc(
+    window: winapi::HWND,
+    message: winapi::UINT,
+    id: winapi::UINT_PTR,
+    not_used: winapi::DWORD) {
+
+	//println!("timer_proc called for HWND: {}", window as i32);
+
+    if let Some(handler) = window.get_event_handler() {
+        handler.on_timer(window, id as usize);
+    }
+}
+
+unsafe extern "system" fn closure_timer_proc(
+    window: winapi::HWND,
+    message: winapi::UINT,
+    id: winapi::UINT_PTR,
+    not_used: winapi::DWORD) {
+
+    fire_timer(id as usize);
+}
+
+//Subclass procedure of an Edit with validation. Checks what the user types
+//or pastes before and after the Edit itself handles it.
+unsafe extern "system" fn validation_proc(
+    window: winapi::HWND,
+    message: winapi::UINT,
+    w_param: winapi::WPARAM,
+    l_param: winapi::LPARAM,
+    _id: winapi::UINT_PTR,
+    _data: winapi::DWORD_PTR) -> winapi::LRESULT {
+
+    let EM_REPLACESEL = 0x00C2;
+    let EM_UNDO = 0x00C7;
+
+    let state = match get_validation(window) {
+        Some(state) => state,
+        None => return comctl32::DefSubclassProc(window, message, w_param, l_param)
+    };
+
+    if message == winapi::WM_NCDESTROY {
+        remove_validation(window);
+
+        return comctl32::DefSubclassProc(window, message, w_param, l_param);
+    }
+
+    let edit = Edit {
+        window: window
+    };
+
+    if masked_input(&edit, &state, message, w_param) {
+        update_error_state(window, &state);
+
+        return 0;
+    }
+
+    let changes_text = match message {
+        winapi::WM_CHAR | winapi::WM_PASTE | winapi::WM_CUT | winapi::WM_CLEAR | winapi::WM_UNDO | winapi::WM_SETTEXT => true,
+        winapi::WM_KEYDOWN => w_param == winapi::VK_DELETE as winapi::WPARAM,
+        _ => message == EM_REPLACESEL || message == EM_UNDO
+    };
+
+    if !changes_text {
+        return comctl32::DefSubclassProc(window, message, w_param, l_param);
+    }
+
+    //Text set from code is flagged but never rejected
+    let from_user = message != winapi::WM_SETTEXT && message != EM_REPLACESEL;
+    let before = if from_user && state.mode.get() == ValidationMode::Reject {
+        Some((edit.get_text(), edit.get_selection()))
+    } else {
+        None
+    };
+
+    let result = comctl32::DefSubclassProc(window, message, w_param, l_param);
+
+    if let Some((text, (start, end))) = before {
+        let new_text = edit.get_text();
+
+        //Clearing the Edit is always allowed, or the user could get stuck
+        if new_text != text && !new_text.is_empty() && !state.check_partial(&new_text) {
+            edit.set_text(&text);
+            edit.set_selection(start, end);
+            user32::MessageBeep(0);
+        }
+    }
+
+    update_error_state(window, &state);
+
+    return result;
+}
+
+//Gives keyboard navigation to windows marked as dialogs, such as
+//FindReplaceDialog. Returns true if the message was handled.
+fn is_dialog_message(message: &mut winapi::MSG) -> bool {
+    let GA_ROOT = 2;
+
+    unsafe {
+        if message.hwnd.is_null() {
+            return false;
+        }
+
+        let root = user32::GetAncestor(message.hwnd, GA_ROOT);
+
+        !root.is_null() && !get_prop(root, "cwnd.dialog").is_null() && user32::IsDialogMessageW(root, message) != 0
+    }
+}
+
+pub struct Application;
+static mut continue_loop: bool = false;
+
+impl Application {
+    pub fn init() {
+        let class_name = "HOWL";
+
+        Application::register_class(class_name, Some(wnd_proc));
+
+        //Needed before any of the common controls can be created
+        let ICC_WIN95_CLASSES = 0x00FF;
+        let controls = winapi::INITCOMMONCONTROLSEX {
+            dwSize: mem::size_of::<winapi::INITCOMMONCONTROLSEX>() as winapi::DWORD,
+            dwICC: ICC_WIN95_CLASSES
+        };
+
+        unsafe {
+            comctl32::InitCommonControlsEx(&controls);
+        }
+
+        INVOKE_WINDOW.with(|invoke_window| {
+            if invoke_window.get().is_null() {
+                let HWND_MESSAGE = -3isize as winapi::HWND;
+
+                invoke_window.set(WindowBuilder::new()
+                    .class_name(class_name)
+                    .parent(HWND_MESSAGE)
+                    .create());
+            }
+        });
+    }
+
+    /// Returns a handle that other threads can use to run code on
+    /// the calling thread. Application::init() must have been called
+    /// from this thread first.
+    pub fn ui_handle() -> UiHandle {
+        unsafe {
+            UiHandle {
+                window: invoke_window() as usize,
+                thread_id: kernel32::GetCurrentThreadId()
+            }
+        }
+    }
+
+    pub fn get_instance() -> winapi::HINSTANCE {
+        unsafe {
+            let instance = kernel32::GetModuleHandleW(ptr::null());
+            if !instance.is_null() {
+                return instance;
+            }
+
+            panic!("GetModuleHandleW error: {}", kernel32::GetLastError());
+        }
+    }
+
+    pub fn register_class(class_name : &str, wnd_proc: winapi::WNDPROC) {
+        let class = winapi::WNDCLASSW {
+            style: winapi::CS_HREDRAW | winapi::CS_VREDRAW | winapi::CS_DBLCLKS,
+            lpfnWndProc: wnd_proc,
+            cbClsExtra: 0,
+            cbWndExtra: 0,
+            hInstance: Application::get_instance(),
+            hIcon: ptr::null_mut(),
+            hCursor: ptr::null_mut(),
+            hbrBackground: winapi::COLOR_WINDOW as winapi::HBRUSH,
+            lpszMenuName: ptr::null_mut(),
+            lpszClassName: to_wchar(class_name).as_ptr()
+        };
+        unsafe {
+            let atom = user32::RegisterClassW(&class);
+
+            if atom == 0 {
+                if  kernel32::GetLastError() != winapi::ERROR_CLASS_ALREADY_EXISTS {
+                    panic!("RegisterClassW error: {}", kernel32::GetLastError());
+                }
+            }
+        }
+    }
+
+    pub fn main_loop() {
+        let mut message = winapi::MSG {
+            hwnd: ptr::null_mut(),
+            message: 0,
+            wParam: 0,
+            lParam: 0,
+            time: 0,
+            pt: winapi::POINT {
+                x: 0,
+                y: 0
+            }
+        };
+
+        let PM_REMOVE = 0x0001;
+        let mut idle_pending = true;
+
+        unsafe {
+            continue_loop = true;
+
+            while continue_loop {
+                    if idle_pending && Application::has_idle_handlers() {
+                        if user32::PeekMessageW(&mut message, ptr::null_mut(), 0, 0, PM_REMOVE) == 0 {
+                            idle_pending = Application::run_idle_handlers();
+
+                            continue;
+                        }
+
+                        if message.message == winapi::WM_QUIT {
+                            break;
+                        }
+                    } else {
+                        let status = user32::GetMessageW(&mut message, ptr::null_mut(), 0, 0);
+                        if status == 0 {
+                            break;
+                        }
+                    }
+
+                    //New messages may have created more idle work
+                    idle_pending = true;
+
+                    if is_dialog_message(&mut message) {
+                        continue;
+                    }
+
+                    user32::TranslateMessage(&message);
+                    user32::DispatchMessageW(&message);
+            }
+        }
+    }
+
+    /// Registers a closure that main_loop() calls whenever the message queue
+    /// is empty. Return true to be called again right away if the queue is
+    /// still empty, or false to wait until the next message arrives.
+    pub fn on_idle<F>(handler: F) where F: FnMut() -> bool + 'static {
+        IDLE_HANDLERS.with(|handlers| handlers.borrow_mut().push(Box::new(handler)));
+    }
+
+    /// Removes all idle handlers registered on this thread.
+    pub fn clear_idle() {
+        IDLE_HANDLERS.with(|handlers| handlers.borrow_mut().clear());
+    }
+
+    fn has_idle_handlers() -> bool {
+        IDLE_HANDLERS.with(|handlers| !handlers.borrow().is_empty())
+    }
+
+    //Returns true if any handler wants to be called again.
+    fn run_idle_handlers() -> bool {
+        //Take the handlers out so that they can register new ones
+        let mut running = IDLE_HANDLERS.with(|handlers| mem::replace(&mut *handlers.borrow_mut(), Vec::new()));
+        let mut more = false;
+
+        for handler in running.iter_mut() {
+            if handler() {
+                more = true;
+            }
+        }
+
+        IDLE_HANDLERS.with(|handlers| {
+            let mut handlers = handlers.borrow_mut();
+            let added = mem::replace(&mut *handlers, running);
+
+            handlers.extend(added);
+        });
+
+        more
+    }
+
+    pub fn exit_loop() {
+        unsafe {
+            continue_loop = false;
+        }
+    }
+}
+
+/// A Send-able handle to the UI thread. Closures given to post() and send()
+/// are executed by Application::main_loop() on the thread that created the
+/// handle. This is the only safe way for a worker thread to update controls.
+#[derive(Clone, Copy)]
+pub struct UiHandle {
+    window: usize,
+    thread_id: winapi::DWORD
+}
+
+impl UiHandle {
+    /// Queues the closure for execution on the UI thread and returns
+    /// immediately.
+    pub fn post<F>(&self, callback: F) where F: FnOnce() + Send + 'static {
+        post_invoke(self.window as winapi::HWND, Box::new(callback));
+    }
+
+    /// Runs the closure on the UI thread and waits for its result. If called
+    /// from the UI thread itself the closure is run directly. Otherwise the
+    /// UI thread must be running Application::main_loop() or this will
+    /// block forever. Returns None if the UI window was destroyed before
+    /// the closure could run.
+    pub fn send<F, R>(&self, callback: F) -> Option<R>
+        where F: FnOnce() -> R + Send + 'static, R: Send + 'static {
+
+        if self.is_ui_thread() {
+            return Some(callback());
+        }
+
+        let (sender, receiver) = mpsc::channel();
+
+        self.post(move || {
+            let _ = sender.send(callback());
+        });
+
+        //The sender is dropped along with the closure if it never runs
+        return receiver.recv().ok();
+    }
+
+    /// Returns true if called from the thread that owns this handle.
+    pub fn is_ui_thread(&self) -> bool {
+        unsafe {
+            return kernel32::GetCurrentThreadId() == self.thread_id;
+        }
+    }
+}
+
+struct Executor {
+    tasks: Vec<Option<Pin<Box<Future<Output = ()>>>>>,
+    free: Vec<usize>
+}
+
+//Wakes a task by posting WM_HOWL_WAKE. PostMessageW may be called from
+//any thread so the waker is Send + Sync.
+struct TaskWaker {
+    window: usize,
+    task: usize
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        unsafe {
+            user32::PostMessageW(self.window as winapi::HWND, WM_HOWL_WAKE, self.task as winapi::WPARAM, 0);
+        }
+    }
+}
+
+/// Runs the future on the calling thread's main loop. The future is
+/// polled from Application::main_loop() whenever it is woken, so it may
+/// freely touch controls.
+pub fn spawn_local<F>(future: F) where F: Future<Output = ()> + 'static {
+    let window = invoke_window();
+    let task = EXECUTOR.with(|executor| {
+        let mut executor = executor.borrow_mut();
+        let future: Pin<Box<Future<Output = ()>>> = Box::pin(future);
+
+        match executor.free.pop() {
+            Some(task) => {
+                executor.tasks[task] = Some(future);
+                task
+            },
+            None => {
+                executor.tasks.push(Some(future));
+                executor.tasks.len() - 1
+            }
+        }
+    });
+
+    Waker::from(Arc::new(TaskWaker {
+        window: window as usize,
+        task: task
+    })).wake();
+}
+
+fn poll_task(window: winapi::HWND, task: usize) {
+    //Take the future out while polling so that it can spawn other tasks.
+    //The slot is not on the free list so it won't be reused meanwhile.
+    let future = EXECUTOR.with(|executor| {
+        executor.borrow_mut().tasks.get_mut(task).and_then(|slot| slot.take())
+    });
+
+    //Spurious wake up of a finished task
+    let mut future = match future {
+        Some(future) => future,
+        None => return
+    };
+
+    let waker = Waker::from(Arc::new(TaskWaker {
+        window: window as usize,
+        task: task
+    }));
+    let mut context = Context::from_waker(&waker);
+    let result = future.as_mut().poll(&mut context);
+
+    EXECUTOR.with(|executor| {
+        let mut executor = executor.borrow_mut();
+
+        match result {
+            Poll::Ready(()) => executor.free.push(task),
+            Poll::Pending => executor.tasks[task] = Some(future)
+        }
+    });
+}
+
+struct DeferredState<R> {
+    result: Option<R>,
+    waker: Option<Waker>
+}
+
+/// A future for work that is run later by the main loop, such as a modal
+/// dialog. The work runs outside of the awaiting task so that other tasks
+/// keep running while the dialog is up. Nothing happens until the future
+/// is first polled.
+pub struct Deferred<R> {
+    state: Rc<RefCell<DeferredState<R>>>,
+    callback: Option<Box<FnOnce() -> R>>
+}
+
+impl <R: 'static> Deferred<R> {
+    pub fn new<F>(callback: F) -> Deferred<R> where F: FnOnce() -> R + 'static {
+        Deferred {
+            state: Rc::new(RefCell::new(DeferredState {
+                result: None,
+                waker: None
+            })),
+            callback: Some(Box::new(callback))
+        }
+    }
+}
+
+impl <R: 'static> Future for Deferred<R> {
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context) -> Poll<R> {
+        let this = self.get_mut();
+
+        //Start the work on the first poll
+        if let Some(callback) = this.callback.take() {
+            let shared = this.state.clone();
+
+            post_invoke(invoke_window(), Box::new(move || {
+                let result = callback();
+                let waker = {
+                    let mut state = shared.borrow_mut();
+
+                    state.result = Some(result);
+                    state.waker.take()
+                };
+
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+            }));
+        }
+
+        let mut state = this.state.borrow_mut();
+
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(context.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+struct TimerEntry {
+    window: usize,
+    interval: Duration,
+    one_shot: bool,
+    //Next expiry for timers running on a VirtualClock. None for real timers.
+    due: Cell<Option<Duration>>,
+    callback: RefCell<Box<FnMut()>>
+}
+
+/// A timer created by Window::every() or Window::after(). The timer is
+/// cancelled when the handle is dropped.
+pub struct Timer {
+    id: usize
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        cancel_timer(self.id);
+    }
+}
+
+fn to_millis(duration: Duration) -> winapi::UINT {
+    (duration.as_secs() * 1000 + duration.subsec_nanos() as u64 / 1_000_000) as winapi::UINT
+}
+
+fn start_timer(window: winapi::HWND, interval: Duration, one_shot: bool, callback: Box<FnMut()>) -> Timer {
+    let id = NEXT_TIMER_ID.with(|next| {
+        let id = next.get();
+
+        next.set(id + 1);
+
+        id
+    });
+    let due = VIRTUAL_NOW.with(|now| now.get()).map(|now| now + interval);
+
+    TIMERS.with(|timers| timers.borrow_mut().insert(id, Rc::new(TimerEntry {
+        window: window as usize,
+        interval: interval,
+        one_shot: one_shot,
+        due: Cell::new(due),
+        callback: RefCell::new(callback)
+    })));
+
+    if due.is_none() {
+        unsafe {
+            user32::SetTimer(window, id as winapi::UINT_PTR, to_millis(interval), Some(closure_timer_proc));
+        }
+    }
+
+    Timer {
+        id: id
+    }
+}
+
+fn cancel_timer(id: usize) {
+    let entry = TIMERS.with(|timers| timers.borrow_mut().remove(&id));
+
+    if let Some(entry) = entry {
+        if entry.due.get().is_none() {
+            unsafe {
+                user32::KillTimer(entry.window as winapi::HWND, id as winapi::UINT_PTR);
+            }
+        }
+    }
+}
+
+fn fire_timer(id: usize) {
+    let entry = TIMERS.with(|timers| timers.borrow().get(&id).cloned());
+
+    let entry = match entry {
+        Some(entry) => entry,
+        None => return
+    };
+
+    if entry.one_shot {
+        cancel_timer(id);
+    }
+
+    //The callback may run a modal loop that fires the same timer again.
+    //Skip such nested calls.
+    if let Ok(mut callback) = entry.callback.try_borrow_mut() {
+        (&mut *callback)();
+    }
+}
+
+/// Drives timers from a manually advanced clock instead of the system
+/// clock. This makes code using Window::every(), Window::after() and
+/// sleep() testable without waiting or running the main loop.
+pub struct VirtualClock;
+
+impl VirtualClock {
+    /// Timers created on this thread after this call run on virtual time,
+    /// starting at zero. Existing timers are unaffected.
+    pub fn install() {
+        VIRTUAL_NOW.with(|now| now.set(Some(Duration::from_millis(0))));
+    }
+
+    /// Switches back to real timers for timers created afterwards.
+    pub fn uninstall() {
+        VIRTUAL_NOW.with(|now| now.set(None));
+    }
+
+    pub fn now() -> Duration {
+        VIRTUAL_NOW.with(|now| now.get()).expect("VirtualClock is not installed.")
+    }
+
+    /// Moves virtual time forward, firing every timer that becomes due in
+    /// expiry order.
+    pub fn advance(duration: Duration) {
+        let target = VirtualClock::now() + duration;
+
+        loop {
+            let next = TIMERS.with(|timers| {
+                timers.borrow().iter()
+                    .filter_map(|(id, entry)| entry.due.get().map(|due| (due, *id)))
+                    .filter(|&(due, _)| due <= target)
+                    .min()
+            });
+
+            let (due, id) = match next {
+                Some(next) => next,
+                None => break
+            };
+
+            VIRTUAL_NOW.with(|now| now.set(Some(due)));
+
+            let entry = TIMERS.with(|timers| timers.borrow().get(&id).cloned());
+
+            if let Some(entry) = entry {
+                //A zero interval would never let time move forward
+                let interval = if entry.interval == Duration::from_millis(0) {
+                    Duration::from_millis(1)
+                } else {
+                    entry.interval
+                };
+
+                entry.due.set(Some(due + interval));
+            }
+
+            fire_timer(id);
+        }
+
+        VIRTUAL_NOW.with(|now| now.set(Some(target)));
+    }
+}
+
+struct SleepState {
+    done: bool,
+    waker: Option<Waker>
+}
+
+/// Future returned by sleep().
+pub struct Sleep {
+    state: Rc<RefCell<SleepState>>,
+    _timer: Timer
+}
+
+/// Completes after the given duration. Uses a one-shot timer on the
+/// calling thread's invoke window, so the main loop must be running.
+pub fn sleep(duration: Duration) -> Sleep {
+    let state = Rc::new(RefCell::new(SleepState {
+        done: false,
+        waker: None
+    }));
+    let shared = state.clone();
+
+    let timer = invoke_window().after(duration, move || {
+        let waker = {
+            let mut state = shared.borrow_mut();
+
+            state.done = true;
+            state.waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    });
+
+    Sleep {
+        state: state,
+        _timer: timer
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context) -> Poll<()> {
+        let mut state = self.state.borrow_mut();
+
+        if state.done {
+            return Poll::Ready(());
+        }
+
+        state.waker = Some(context.waker().clone());
+
+        Poll::Pending
+    }
+}
+
+pub struct WindowBuilder<'a> {
+	x: i32,
+	y: i32,
+	width: i32,
+	height: i32,
+	style: winapi::DWORD,
+	extra_style: winapi::DWORD,
+	class_name: &'a str,
+	title: &'a str,
+	parent: winapi::HWND,
+	id: u16
+}
+
+impl <'a> WindowBuilder<'a> {
+	pub fn new() -> WindowBuilder<'a> {
+		WindowBuilder {
+			x: winapi::CW_USEDEFAULT,
+			y: winapi::CW_USEDEFAULT,
+			width: winapi::CW_USEDEFAULT,
+			height: winapi::CW_USEDEFAULT,
+			style: 0,
+			extra_style: 0,
+			class_name: "",
+			title: "",
+			parent: 0 as winapi::HWND,
+			id: 0
+		}
+	}
+	pub fn style(&'a mut self, style: winapi::DWORD) -> &mut WindowBuilder {
+		self.style = style;
+
+		self
+	}
+	pub fn extra_style(&'a mut self, extra_style: winapi::DWORD) -> &mut WindowBuilder {
+		self.extra_style = extra_style;
+
+		self
+	}
+	pub fn parent(&'a mut self, parent: winapi::HWND) -> &mut WindowBuilder {
+		self.parent = parent;
+
+		self
+	}
+	pub fn position(&'a mut self, x: i32, y: i32) -> &mut WindowBuilder {
+		self.x = x;
+		self.y = y;
+
+		self
+	}
+	pub fn size(&'a mut self, width: i32, height: i32) -> &mut WindowBuilder {
+		self.width = width;
+		self.height = height;
+
+		self
+	}
+
+	pub fn id(&'a mut self, id: u16) -> &mut WindowBuilder {
+		self.id = id;
+
+		self
+	}
+
+	pub fn class_name(&'a mut self, class_name: &'a str) -> &mut WindowBuilder {
+		self.class_name = class_name;
+
+		self
+	}
+	pub fn title(&'a mut self, title: &'a str) -> &mut WindowBuilder {
+		self.title = title;
+
+		self
+	}
+
+	pub fn button(&'a mut self, title: &'a str) -> &mut WindowBuilder {
+		self.title(title)
+		.class_name("BUTTON")
+		.style(winapi::WS_VISIBLE | winapi::WS_TABSTOP | winapi::WS_CHILD | winapi::BS_PUSHBUTTON)
+	}
+
+	pub fn checkbox(&'a mut self, title: &'a str) -> &mut WindowBuilder {
+		self.title(title)
+		.class_name("BUTTON")
+		.style(winapi::WS_VISIBLE | winapi::WS_TABSTOP | winapi::WS_CHILD | winapi::BS_CHECKBOX)
+	}
+
+	pub fn frame(&'a mut self, title: &'a str) -> &mut WindowBuilder {
+		self.title(title)
+		.class_name("HOWL")
+		.style(winapi::WS_THICKFRAME | winapi::WS_MINIMIZEBOX | winapi::WS_MAXIMIZEBOX | winapi::WS_SYSMENU)
+		.extra_style(winapi::WS_EX_CLIENTEDGE)
+	}
+
+	pub fn create(&self) -> winapi::HWND {
+		unsafe {
+		    let window =  user32::CreateWindowExW(
+		        self.extra_style,
+		        to_wchar(self.class_name).as_ptr(),
+		        to_wchar(self.title).as_ptr(),
+		        self.style,
+		        self.x,
+		        self.y,
+		        self.width,
+		        self.height,
+		        self.parent,
+		        self.id as winapi::HMENU,
+		        Application::get_instance(),
+		        ptr::null_mut());
+
+		    if window.is_null() {
+		        panic!("CreateWindowExW error: {}", kernel32::GetLastError());
+		    }
+
+		    window
+		}
+	}
+}
+
+pub trait Window {
+	fn get_hwnd(&self) -> winapi::HWND;
+
+	fn show(&self) {
+		unsafe {
+			user32::ShowWindow(self.get_hwnd(), 5);
+		}
+	}
+
+	fn hide(&self) {
+		unsafe {
+			user32::ShowWindow(self.get_hwnd(), 0);
+		}
+	}
+
+    fn resize(&self, x : i32, y : i32, width : i32, height : i32) {
+        unsafe {
+            user32::MoveWindow(self.get_hwnd(), x, y, width, height, 1);
+        }
+    }
+
+    fn size(&self, width: &mut i32, height: &mut i32) {
+        unsafe {
+            let mut rect = winapi::RECT {
+                    top: 0, left: 0, right: 0, bottom: 0
+            };
+            user32::GetWindowRect(self.get_hwnd(), &mut rect);
+            println!("size(): {:?}", rect);
+            *width = rect.right - rect.left;
+            *height = rect.bottom - rect.top;
+        }
+    }
+
+    fn get_position(&self) -> winapi::RECT {
+        unsafe {
+            let mut rect = winapi::RECT {
+                    top: 0, left: 0, right: 0, bottom: 0
+            };
+            user32::GetWindowRect(self.get_hwnd(), &mut rect);
+            println!("{:?}", rect);
 
             let mut point = winapi::POINT {
                 x: rect.left,
                 y: rect.top
             };
 
-            user32::MapWindowPoints(0 as winapi::HWND,
-                user32::GetParent(self.get_hwnd()),
-                &mut point as *mut winapi::POINT, 1);
+            user32::MapWindowPoints(0 as winapi::HWND,
+                user32::GetParent(self.get_hwnd()),
+                &mut point as *mut winapi::POINT, 1);
+
+            rect.left = point.x;
+            rect.top = point.y;
+
+            let mut point = winapi::POINT {
+                x: rect.right,
+                y: rect.bottom
+            };
+
+            user32::MapWindowPoints(0 as winapi::HWND,
+                user32::GetParent(self.get_hwnd()),
+                &mut point as *mut winapi::POINT, 1);
+
+            rect.right = point.x;
+            rect.bottom = point.y;
+
+            return rect;
+        }
+    }
+
+    fn get_text_length(&self) -> usize {
+		unsafe {
+			return user32::SendMessageW(self.get_hwnd(), winapi::WM_GETTEXTLENGTH, 0, 0)
+                as usize;
+		}
+	}
+
+	fn set_text(&self, txt : &str) {
+		unsafe {
+			user32::SendMessageW(self.get_hwnd(), winapi::WM_SETTEXT, 0, to_wchar(txt).as_ptr() as winapi::LPARAM);
+		}
+	}
+
+    fn get_text(&self) -> String {
+        let size = self.get_text_length() + 1; //Win32 adds the NULL. So we need one extra space
+        let mut v : Vec<u16> = Vec::with_capacity(size as usize);
+
+		unsafe {
+            v.set_len((size - 1) as usize);
+			user32::SendMessageW(self.get_hwnd(), winapi::WM_GETTEXT, size as winapi::WPARAM, v.as_ptr() as winapi::LPARAM);
+		}
+
+        return String::from_utf16(&v[..]).unwrap();
+	}
+
+    fn attach_event_handler(&self, handler: &WindowEventHandler) {
+        unsafe {
+            let raw_obj: raw::TraitObject = mem::transmute(handler);
+
+            let prop = to_wchar("cwnd.data");
+            user32::SetPropW(self.get_hwnd(), prop.as_ptr(), raw_obj.data as winapi::HANDLE);
+            let prop = to_wchar("cwnd.vtable");
+            user32::SetPropW(self.get_hwnd(), prop.as_ptr(), raw_obj.vtable as winapi::HANDLE);
+        }
+    }
+
+    fn detach_event_handler(&mut self) {
+        unsafe {
+            let prop = to_wchar("cwnd.data");
+            user32::RemovePropW(self.get_hwnd(), prop.as_ptr());
+            let prop = to_wchar("cwnd.vtable");
+            user32::RemovePropW(self.get_hwnd(), prop.as_ptr());
+        }
+    }
+
+    fn get_event_handler(&self) -> Option<&mut WindowEventHandler> {
+        unsafe {
+            let prop = to_wchar("cwnd.data");
+            let data = user32::GetPropW(self.get_hwnd(), prop.as_ptr());
+            let prop = to_wchar("cwnd.vtable");
+            let vtable = user32::GetPropW(self.get_hwnd(), prop.as_ptr());
+
+            if data == ptr::null_mut() || vtable == ptr::null_mut() {
+                println!("wnd_proc Can not find attached data for HWND: {}.", self.get_hwnd() as i32);
+                return None;
+            }
+
+            let synthesized: &mut WindowEventHandler =
+                mem::transmute(raw::TraitObject {
+                 data: data as *const _ as *mut (),
+                 vtable: vtable as *const _ as *mut ()
+                });
+
+            return Some(synthesized);
+        }
+    }
+
+    fn set_timer(&mut self, id : usize, interval : usize) {
+        unsafe {
+            user32::SetTimer(self.get_hwnd(), id as winapi::UINT_PTR, interval as winapi::UINT, Some(timer_proc));
+        }
+    }
+    fn kill_timer(&mut self, id : usize) {
+        unsafe {
+            user32::KillTimer(self.get_hwnd(), id as winapi::UINT_PTR);
+        }
+    }
+    /// Sets the background color of a Frame or Canvas, or of a child
+    /// control such as Edit, ListBox or Checkbox. Controls need a howl
+    /// window (Frame or Canvas) as their parent for this to work. The alpha
+    /// channel is ignored.
+    fn set_background_color(&self, color: Color) {
+        set_color_prop(self.get_hwnd(), "cwnd.bgcolor", color);
+        self.invalidate();
+    }
+
+    fn get_background_color(&self) -> Option<Color> {
+        get_color_prop(self.get_hwnd(), "cwnd.bgcolor").map(Color::from_colorref)
+    }
+
+    /// Sets the text color of a child control. For a Frame or Canvas this
+    /// is the initial text color used in on_paint().
+    fn set_text_color(&self, color: Color) {
+        set_color_prop(self.get_hwnd(), "cwnd.fgcolor", color);
+        self.invalidate();
+    }
+
+    fn get_text_color(&self) -> Option<Color> {
+        get_color_prop(self.get_hwnd(), "cwnd.fgcolor").map(Color::from_colorref)
+    }
+
+    /// Shows the text in a tooltip when the mouse rests over the window.
+    fn set_tooltip(&self, text: &str) {
+        TOOLTIP_CALLBACKS.with(|callbacks| callbacks.borrow_mut().remove(&(self.get_hwnd() as usize)));
+        add_tool(self.get_hwnd(), to_wchar(text).as_ptr());
+    }
+
+    /// Asks the closure for the tooltip text at the mouse position, in
+    /// client coordinates. Return None for spots without a tip. The text
+    /// follows the mouse inside howl windows such as Canvas; other
+    /// controls ask once each time the tip is shown.
+    fn set_tooltip_callback<F>(&self, callback: F)
+        where F: Fn(i32, i32) -> Option<String> + 'static, Self: Sized {
+
+        let LPSTR_TEXTCALLBACKW = -1isize as *const u16;
+
+        TOOLTIP_CALLBACKS.with(|callbacks| callbacks.borrow_mut().insert(self.get_hwnd() as usize, Rc::new(TooltipCallback {
+            callback: Box::new(callback),
+            last_text: RefCell::new(None)
+        })));
+        add_tool(self.get_hwnd(), LPSTR_TEXTCALLBACKW);
+    }
+
+    fn remove_tooltip(&self) {
+        let TTM_DELTOOLW = 0x0433;
+        let info = new_tool_info(self.get_hwnd());
+
+        TOOLTIP_CALLBACKS.with(|callbacks| callbacks.borrow_mut().remove(&(self.get_hwnd() as usize)));
+
+        unsafe {
+            user32::SendMessageW(get_tooltip_control(self.get_hwnd()), TTM_DELTOOLW, 0, &info as *const winapi::TOOLINFOW as winapi::LPARAM);
+        }
+    }
+
+    /// Returns the fixed tooltip text set with set_tooltip().
+    fn get_tooltip(&self) -> Option<String> {
+        let TTM_GETTEXTW = 0x0438;
+        let mut info = new_tool_info(self.get_hwnd());
+        let mut v : Vec<u16> = vec![0; 1024];
+
+        info.lpszText = v.as_mut_ptr();
+
+        unsafe {
+            user32::SendMessageW(get_tooltip_control(self.get_hwnd()), TTM_GETTEXTW, v.len() as winapi::WPARAM,
+                &mut info as *mut winapi::TOOLINFOW as winapi::LPARAM);
+        }
+
+        let len = v.iter().position(|c| *c == 0).unwrap_or(v.len());
+
+        if len == 0 {
+            return None;
+        }
+
+        v.truncate(len);
+
+        Some(String::from_utf16_lossy(&v[..]))
+    }
+
+    /// Sets how long the mouse must rest before tooltips appear, for all
+    /// windows under the same top level window.
+    fn set_tooltip_delay(&self, milliseconds: u32) {
+        let TTM_SETDELAYTIME = 0x0403;
+        let TTDT_INITIAL = 3;
+
+        unsafe {
+            user32::SendMessageW(get_tooltip_control(self.get_hwnd()), TTM_SETDELAYTIME, TTDT_INITIAL, milliseconds as winapi::LPARAM);
+        }
+    }
+
+    /// Sets up the window's own scroll bar for content of the given size,
+    /// of which page is visible at a time. The bar hides itself when the
+    /// content fits.
+    fn set_scroll_range(&self, bar: ScrollBar, size: i32, page: i32) {
+        let SIF_RANGE = 0x0001;
+        let SIF_PAGE = 0x0002;
+        let mut info = new_scroll_info(SIF_RANGE | SIF_PAGE);
+
+        info.nMax = if size > 0 {size - 1} else {0};
+        info.nPage = if page > 0 {page as winapi::UINT} else {0};
+
+        unsafe {
+            user32::SetScrollInfo(self.get_hwnd(), bar as i32, &info, 1);
+        }
+    }
+
+    fn get_scroll_position(&self, bar: ScrollBar) -> i32 {
+        let SIF_POS = 0x0004;
+        let mut info = new_scroll_info(SIF_POS);
+
+        unsafe {
+            user32::GetScrollInfo(self.get_hwnd(), bar as i32, &mut info);
+        }
+
+        return info.nPos;
+    }
+
+    /// Moves the scroll box. Returns the position after clamping it to the
+    /// range.
+    fn set_scroll_position(&self, bar: ScrollBar, position: i32) -> i32 {
+        let SIF_POS = 0x0004;
+        let mut info = new_scroll_info(SIF_POS);
+
+        info.nPos = position;
+
+        unsafe {
+            user32::SetScrollInfo(self.get_hwnd(), bar as i32, &info, 1);
+        }
+
+        return self.get_scroll_position(bar);
+    }
+
+    /// Moves the scroll box the way the standard scroll bar request in
+    /// on_scroll() asks for. line is the distance of one arrow click.
+    /// Returns the new position.
+    fn apply_scroll_request(&self, bar: ScrollBar, request: u16, line: i32) -> i32 {
+        let SB_LINEUP = 0;
+        let SB_LINEDOWN = 1;
+        let SB_PAGEUP = 2;
+        let SB_PAGEDOWN = 3;
+        let SB_THUMBPOSITION = 4;
+        let SB_THUMBTRACK = 5;
+        let SB_TOP = 6;
+        let SB_BOTTOM = 7;
+        let SIF_ALL = 0x0017;
+        let mut info = new_scroll_info(SIF_ALL);
+
+        unsafe {
+            user32::GetScrollInfo(self.get_hwnd(), bar as i32, &mut info);
+        }
+
+        let position = match request {
+            SB_LINEUP => info.nPos - line,
+            SB_LINEDOWN => info.nPos + line,
+            SB_PAGEUP => info.nPos - info.nPage as i32,
+            SB_PAGEDOWN => info.nPos + info.nPage as i32,
+            SB_THUMBPOSITION | SB_THUMBTRACK => info.nTrackPos,
+            SB_TOP => info.nMin,
+            SB_BOTTOM => info.nMax,
+            _ => info.nPos
+        };
+
+        return self.set_scroll_position(bar, position);
+    }
+
+    /// Returns the part of the client area not covered by an attached
+    /// ToolBar or StatusBar. Lay out child controls inside this rectangle.
+    fn get_client_area(&self) -> winapi::RECT {
+        let mut area = winapi::RECT {
+            top: 0, left: 0, right: 0, bottom: 0
+        };
+
+        unsafe {
+            user32::GetClientRect(self.get_hwnd(), &mut area);
+        }
+
+        area.top = area.top + bar_height(get_prop(self.get_hwnd(), "cwnd.toolbar") as winapi::HWND);
+        area.bottom = area.bottom - bar_height(get_prop(self.get_hwnd(), "cwnd.statusbar") as winapi::HWND);
+
+        if area.bottom < area.top {
+            area.bottom = area.top;
+        }
+
+        return area;
+    }
+
+    /// Sets the font used by a control. The font must outlive the window
+    /// or be replaced first.
+    fn set_font(&self, font: &Font) {
+        unsafe {
+            user32::SendMessageW(self.get_hwnd(), winapi::WM_SETFONT, font.get_handle() as winapi::WPARAM, 1);
+        }
+    }
+
+    /// Marks the whole client area for repainting. The window gets a
+    /// paint event the next time the message queue is empty.
+    fn invalidate(&self) {
+        unsafe {
+            user32::InvalidateRect(self.get_hwnd(), ptr::null(), 1);
+        }
+    }
+
+    /// Marks part of the client area for repainting.
+    fn invalidate_rect(&self, rect: &winapi::RECT) {
+        unsafe {
+            user32::InvalidateRect(self.get_hwnd(), rect, 1);
+        }
+    }
+
+    /// Paints any invalid region right away instead of waiting for the
+    /// message queue to empty.
+    fn update_now(&self) {
+        unsafe {
+            user32::UpdateWindow(self.get_hwnd());
+        }
+    }
+
+    /// Calls the closure repeatedly at the given interval until the
+    /// returned Timer is dropped.
+    fn every<F>(&self, interval: Duration, callback: F) -> Timer
+        where F: FnMut() + 'static, Self: Sized {
+
+        start_timer(self.get_hwnd(), interval, false, Box::new(callback))
+    }
+
+    /// Calls the closure once after the given delay, unless the returned
+    /// Timer is dropped first.
+    fn after<F>(&self, delay: Duration, callback: F) -> Timer
+        where F: FnOnce() + 'static, Self: Sized {
+
+        let mut callback = Some(callback);
+
+        start_timer(self.get_hwnd(), delay, true, Box::new(move || {
+            if let Some(callback) = callback.take() {
+                callback();
+            }
+        }))
+    }
+
+    fn message_box(&self, msg: &str) {
+        let msg = to_wchar(msg);
+        let title = to_wchar("Message");
+
+        unsafe {
+    	       user32::MessageBoxW(self.get_hwnd(), msg.as_ptr(), title.as_ptr(), winapi::MB_OK);
+        }
+    }
+    fn error_box(&self, msg: &str) {
+        let msg = to_wchar(msg);
+        let title = to_wchar("Error");
+
+        unsafe {
+    	       user32::MessageBoxW(self.get_hwnd(), msg.as_ptr(), title.as_ptr(), winapi::MB_OK | winapi::MB_ICONERROR);
+        }
+    }
+
+    fn question_box(&self, msg: &str) -> bool {
+        let msg = to_wchar(msg);
+        let title = to_wchar("Question");
+
+        unsafe {
+    	       return user32::MessageBoxW(self.get_hwnd(), msg.as_ptr(), title.as_ptr(),
+                winapi::MB_YESNO | winapi::MB_ICONQUESTION) == 6; //winapi::IDYES
+        }
+    }
+
+    fn message_box_async(&self, msg: &str) -> Deferred<()> {
+        let window = self.get_hwnd();
+        let msg = msg.to_string();
+
+        Deferred::new(move || window.message_box(&msg))
+    }
+
+    fn error_box_async(&self, msg: &str) -> Deferred<()> {
+        let window = self.get_hwnd();
+        let msg = msg.to_string();
+
+        Deferred::new(move || window.error_box(&msg))
+    }
+
+    fn question_box_async(&self, msg: &str) -> Deferred<bool> {
+        let window = self.get_hwnd();
+        let msg = msg.to_string();
+
+        Deferred::new(move || window.question_box(&msg))
+    }
+
+    fn confirm_box_async(&self, msg: &str) -> Deferred<bool> {
+        let window = self.get_hwnd();
+        let msg = msg.to_string();
+
+        Deferred::new(move || window.confirm_box(&msg))
+    }
+
+    fn confirm_box(&self, msg: &str) -> bool {
+        let msg = to_wchar(msg);
+        let title = to_wchar("Confirm");
+
+        unsafe {
+    	       return user32::MessageBoxW(self.get_hwnd(), msg.as_ptr(), title.as_ptr(),
+                winapi::MB_OKCANCEL | winapi::MB_ICONINFORMATION) == 1; //winapi::IDOK
+        }
+    }
+}
+
+impl Window for winapi::HWND {
+	fn get_hwnd(&self) -> winapi::HWND {
+		return *self;
+	}
+}
+
+pub struct Button {
+    window : winapi::HWND
+}
+
+impl Window for Button {
+    fn get_hwnd(&self) -> winapi::HWND {
+		return self.window;
+	}
+}
+
+impl Button {
+    pub fn new(parent: &Window, id: u16, title: &str, x: i32, y: i32, width: i32, height: i32) -> Button {
+        let wnd = WindowBuilder::new()
+            .title(title)
+            .class_name("BUTTON")
+            .style(winapi::WS_VISIBLE | winapi::WS_TABSTOP | winapi::WS_CHILD | winapi::BS_PUSHBUTTON)
+            .position(x, y)
+            .size(width, height)
+            .parent(parent.get_hwnd())
+            .id(id)
+            .create();
+        Button {
+            window: wnd
+        }
+    }
+
+    /// Shows the image on the button. The image must outlive the button
+    /// or be replaced first.
+    pub fn set_image(&self, image: &Image) {
+        let BM_SETIMAGE = 0x00F7;
+        let IMAGE_BITMAP = 0;
+
+        unsafe {
+            user32::SendMessageW(self.window, BM_SETIMAGE, IMAGE_BITMAP, image.get_bitmap() as winapi::LPARAM);
+        }
+    }
+}
+
+pub struct Edit {
+    window : winapi::HWND
+}
+
+impl Window for Edit {
+    fn get_hwnd(&self) -> winapi::HWND {
+		return self.window;
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextCase {
+    Normal,
+    /// Typed letters are converted to upper case.
+    Upper,
+    /// Typed letters are converted to lower case.
+    Lower
+}
+
+#[derive(Clone, Debug)]
+pub struct EditOptions {
+    pub multi_line: bool,
+    /// Show bullets in place of the characters. Single line only.
+    pub password: bool,
+    pub align: TextAlign,
+    pub case: TextCase,
+    /// Only let the user type digits. Pasted and programmatic text is
+    /// not checked.
+    pub number_only: bool,
+    pub read_only: bool,
+    /// Break long lines at the width of a multi-line Edit.
+    pub word_wrap: bool,
+    /// Scroll the text sideways when it gets longer than the width, with
+    /// a scroll bar if multi-line.
+    pub horizontal_scroll: bool,
+    /// Keep showing the selection while the Edit does not have the focus.
+    pub keep_selection: bool,
+    /// Grayed hint shown while the Edit is empty. Single line only.
+    pub placeholder: Option<String>
+}
+
+impl EditOptions {
+    pub fn new() -> EditOptions {
+        EditOptions {
+            multi_line: false,
+            password: false,
+            align: TextAlign::Left,
+            case: TextCase::Normal,
+            number_only: false,
+            read_only: false,
+            word_wrap: true,
+            horizontal_scroll: false,
+            keep_selection: false,
+            placeholder: None
+        }
+    }
+}
+
+impl Edit {
+    pub fn new(parent: &Window, x: i32, y: i32, width: i32, height: i32, multi_line:bool) -> Edit {
+        let mut options = EditOptions::new();
+
+        options.multi_line = multi_line;
+
+        Edit::with_options(parent, 0, x, y, width, height, &options)
+    }
+
+    pub fn with_options(parent: &Window, id: u16, x: i32, y: i32, width: i32, height: i32, options: &EditOptions) -> Edit {
+        let ES_CENTER           = 0x0001;
+        let ES_RIGHT            = 0x0002;
+        let ES_MULTILINE        = 0x0004;
+        let ES_UPPERCASE        = 0x0008;
+        let ES_LOWERCASE        = 0x0010;
+        let ES_PASSWORD         = 0x0020;
+        let ES_AUTOVSCROLL      = 0x0040;
+        let ES_AUTOHSCROLL      = 0x0080;
+        let ES_NOHIDESEL        = 0x0100;
+        let ES_READONLY         = 0x0800;
+        let ES_WANTRETURN       = 0x1000;
+        let ES_NUMBER           = 0x2000;
+
+        let mut style : winapi::DWORD  = winapi::WS_CHILD | winapi::WS_VISIBLE;
+
+    	if options.multi_line {
+    		style = style | winapi::WS_VSCROLL |
+                ES_WANTRETURN | ES_MULTILINE | ES_AUTOVSCROLL;
+
+            if !options.word_wrap {
+                style = style | ES_AUTOHSCROLL;
+            }
+
+            if options.horizontal_scroll {
+                style = style | winapi::WS_HSCROLL;
+            }
+        } else if options.password {
+            style = style | ES_PASSWORD;
+        }
+
+        if options.horizontal_scroll {
+            style = style | ES_AUTOHSCROLL;
+        }
+
+        style = style | match options.align {
+            TextAlign::Left => 0,
+            TextAlign::Center => ES_CENTER,
+            TextAlign::Right => ES_RIGHT
+        };
+
+        style = style | match options.case {
+            TextCase::Normal => 0,
+            TextCase::Upper => ES_UPPERCASE,
+            TextCase::Lower => ES_LOWERCASE
+        };
+
+        if options.number_only {
+            style = style | ES_NUMBER;
+        }
+
+        if options.read_only {
+            style = style | ES_READONLY;
+        }
+
+        if options.keep_selection {
+            style = style | ES_NOHIDESEL;
+        }
+
+        let wnd = WindowBuilder::new()
+            .class_name("EDIT")
+            .style(style)
+            .position(x, y)
+            .size(width, height)
+            .parent(parent.get_hwnd())
+            .id(id)
+            .create();
+        let edit = Edit {
+            window: wnd
+        };
+
+        if let Some(ref placeholder) = options.placeholder {
+            edit.set_placeholder(placeholder);
+        }
+
+        edit
+    }
+
+    /// Sets the grayed hint shown while the Edit is empty, even when it
+    /// has the focus. Needs version 6 of the common controls, which the
+    /// application manifest has to ask for.
+    pub fn set_placeholder(&self, txt: &str) {
+        let EM_SETCUEBANNER = 0x1501;
+        let txt = to_wchar(txt);
+
+        unsafe {
+            user32::SendMessageW(self.window, EM_SETCUEBANNER, 1, txt.as_ptr() as winapi::LPARAM);
+        }
+    }
+
+    /// Returns the placeholder, or an empty string if there is none or
+    /// the common controls are too old to support it.
+    pub fn get_placeholder(&self) -> String {
+        let EM_GETCUEBANNER = 0x1502;
+        let mut v : Vec<u16> = vec![0; 256];
+
+        unsafe {
+            if user32::SendMessageW(self.window, EM_GETCUEBANNER, v.as_mut_ptr() as winapi::WPARAM, v.len() as winapi::LPARAM) == 0 {
+                return String::new();
+            }
+        }
+
+        let len = v.iter().position(|&c| c == 0).unwrap_or(v.len());
+
+        return String::from_utf16_lossy(&v[..len]);
+    }
+
+    pub fn set_read_only(&self, read_only : bool) {
+        let EM_SETREADONLY = 0x00CF;
+
+        unsafe {
+            user32::SendMessageW(self.window, EM_SETREADONLY, if read_only {1} else {0}, 0);
+        }
+    }
+
+    pub fn append_text(&self, txt : &str) {
+    	let prev_len = self.get_text_length();
+        let txt = to_wchar(txt);
+        let EM_SETSEL               = 0x00B1;
+        let EM_REPLACESEL           = 0x00C2;
+
+        unsafe {
+        	user32::SendMessageW(self.window, EM_SETSEL, prev_len as winapi::WPARAM, prev_len as winapi::LPARAM);
+        	user32::SendMessageW(self.window, EM_REPLACESEL, 0, txt.as_ptr() as winapi::LPARAM);
+        }
+    }
+
+    /// Sets the id reported to on_command() and on_text_change().
+    pub fn set_id(&self, id: u16) {
+        unsafe {
+            user32::SetWindowLongW(self.window, winapi::GWL_ID, id as winapi::LONG);
+        }
+    }
+
+    pub fn get_id(&self) -> u16 {
+        unsafe {
+            return user32::GetDlgCtrlID(self.window) as u16;
+        }
+    }
+
+    /// Returns the start and end of the selection. Positions count UTF-16
+    /// code units, as everywhere in the Edit API.
+    pub fn get_selection(&self) -> (i32, i32) {
+        let EM_GETSEL = 0x00B0;
+        let mut start: winapi::DWORD = 0;
+        let mut end: winapi::DWORD = 0;
+
+        unsafe {
+            user32::SendMessageW(self.window, EM_GETSEL,
+                &mut start as *mut winapi::DWORD as winapi::WPARAM,
+                &mut end as *mut winapi::DWORD as winapi::LPARAM);
+        }
+
+        (start as i32, end as i32)
+    }
+
+    /// Selects the text from start up to end. Pass 0 and -1 to select
+    /// everything, or the same position twice to just move the caret.
+    pub fn set_selection(&self, start: i32, end: i32) {
+        let EM_SETSEL = 0x00B1;
+
+        unsafe {
+            user32::SendMessageW(self.window, EM_SETSEL, start as winapi::WPARAM, end as winapi::LPARAM);
+        }
+    }
+
+    /// Replaces the selection, or inserts at the caret if nothing is
+    /// selected. The change can be undone.
+    pub fn replace_selection(&self, txt: &str) {
+        let EM_REPLACESEL = 0x00C2;
+        let txt = to_wchar(txt);
+
+        unsafe {
+            user32::SendMessageW(self.window, EM_REPLACESEL, 1, txt.as_ptr() as winapi::LPARAM);
+        }
+    }
+
+    /// Returns the end of the selection. That is where the caret is,
+    /// except after the user selected backward; the Edit does not report
+    /// which end of the selection holds the caret.
+    pub fn caret_position(&self) -> i32 {
+        let (_, end) = self.get_selection();
+
+        end
+    }
+
+    pub fn line_count(&self) -> i32 {
+        let EM_GETLINECOUNT = 0x00BA;
+
+        unsafe {
+            return user32::SendMessageW(self.window, EM_GETLINECOUNT, 0, 0) as i32;
+        }
+    }
+
+    /// Returns the text of the given line, without the line break. Word
+    /// wrapped lines count separately. Returns an empty string if there is
+    /// no such line.
+    pub fn get_line(&self, line: i32) -> String {
+        let EM_LINEINDEX = 0x00BB;
+        let EM_LINELENGTH = 0x00C1;
+        let EM_GETLINE = 0x00C4;
+
+        if line < 0 || line >= self.line_count() {
+            return String::new();
+        }
+
+        unsafe {
+            let start = user32::SendMessageW(self.window, EM_LINEINDEX, line as winapi::WPARAM, 0);
+
+            if start < 0 {
+                return String::new();
+            }
+
+            let length = user32::SendMessageW(self.window, EM_LINELENGTH, start as winapi::WPARAM, 0) as usize;
+
+            //The size in the first word can not describe longer lines
+            if length >= u16::MAX as usize {
+                let text: Vec<u16> = self.get_text().encode_utf16().collect();
+                let start = (start as usize).min(text.len());
+                let end = (start + length).min(text.len());
+
+                return String::from_utf16_lossy(&text[start..end]);
+            }
+
+            //EM_GETLINE reads the buffer size from the first word of the
+            //buffer, which needs room for at least that word
+            let mut v : Vec<u16> = vec![0; length + 1];
+            v[0] = (length + 1) as u16;
+
+            let copied = user32::SendMessageW(self.window, EM_GETLINE, line as winapi::WPARAM, v.as_mut_ptr() as winapi::LPARAM) as usize;
+
+            v.truncate(copied);
+
+            return String::from_utf16_lossy(&v);
+        }
+    }
+
+    pub fn scroll_to_caret(&self) {
+        let EM_SCROLLCARET = 0x00B7;
+
+        unsafe {
+            user32::SendMessageW(self.window, EM_SCROLLCARET, 0, 0);
+        }
+    }
+
+    /// Limits the length of the text. The limit applies to typing, pasting,
+    /// replace_selection() and append_text(); only set_text() can go past
+    /// it. 0 restores the default limit.
+    pub fn set_max_length(&self, length: usize) {
+        let EM_SETLIMITTEXT = 0x00C5;
+
+        unsafe {
+            user32::SendMessageW(self.window, EM_SETLIMITTEXT, length as winapi::WPARAM, 0);
+        }
+    }
+
+    /// Undoes the last change. Calling it again redoes the change.
+    pub fn undo(&self) -> bool {
+        let EM_UNDO = 0x00C7;
+
+        unsafe {
+            return user32::SendMessageW(self.window, EM_UNDO, 0, 0) != 0;
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        let EM_CANUNDO = 0x00C6;
+
+        unsafe {
+            return user32::SendMessageW(self.window, EM_CANUNDO, 0, 0) != 0;
+        }
+    }
+
+    /// Checks the text as the user edits it. Invalid text is turned red or
+    /// rejected, depending on mode. Text set from code is only flagged.
+    /// The red color works with any parent window.
+    pub fn set_validator(&self, validator: Validator, mode: ValidationMode) {
+        let state = install_validation(self.window);
+
+        *state.validator.borrow_mut() = Some(validator);
+        state.mode.set(mode);
+        update_error_state(self.window, &state);
+    }
+
+    /// Makes the user fill in a fixed layout. Clears the text. A validator
+    /// can be set as well, to check the complete text.
+    pub fn set_input_mask(&self, mask: InputMask) {
+        let state = install_validation(self.window);
+
+        self.set_text(&mask.empty_text());
+        *state.mask.borrow_mut() = Some(mask);
+        update_error_state(self.window, &state);
+    }
+
+    /// Removes the validator and input mask.
+    pub fn remove_validation(&self) {
+        remove_validation(self.window);
+    }
+
+    /// True if the text passes the validator and fills the input mask.
+    /// Unlike the red error state this is false for an empty Edit that
+    /// needs text.
+    pub fn is_valid(&self) -> bool {
+        match get_validation(self.window) {
+            Some(state) => state.check(&self.get_text()),
+            None => true
+        }
+    }
+}
+
+//Background of an Edit whose text fails validation
+const INVALID_BACKGROUND: winapi::COLORREF = 0x00DCDCFF;
+
+/// What happens when the user types or pastes text that a Validator
+/// rejects.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ValidationMode {
+    /// The change is undone with a beep. The validator has to accept
+    /// partial input, like "1" on the way to "12".
+    Reject,
+    /// The change is kept and the Edit turns red until the text is valid.
+    Flag
+}
+
+/// Decides whether the text of an Edit is acceptable. Besides the check
+/// of complete text, a validator has a check of partial text that
+/// ValidationMode::Reject applies to each keystroke.
+#[derive(Clone)]
+pub struct Validator {
+    check: Rc<Fn(&str) -> bool>,
+    partial: Rc<Fn(&str) -> bool>
+}
+
+impl Validator {
+    /// A validator that uses check for partial text too. In Reject mode
+    /// it has to accept every prefix of valid text; see with_partial().
+    pub fn new<F>(check: F) -> Validator where F: Fn(&str) -> bool + 'static {
+        let check: Rc<Fn(&str) -> bool> = Rc::new(check);
+
+        Validator {
+            check: check.clone(),
+            partial: check
+        }
+    }
+
+    /// A validator with a separate check for text still being typed.
+    /// partial should accept anything that can be completed to valid text.
+    pub fn with_partial<F, P>(check: F, partial: P) -> Validator
+        where F: Fn(&str) -> bool + 'static, P: Fn(&str) -> bool + 'static {
+
+        Validator {
+            check: Rc::new(check),
+            partial: Rc::new(partial)
+        }
+    }
+
+    /// A whole number from min to max, inclusive.
+    pub fn integer_range(min: i64, max: i64) -> Validator {
+        Validator::with_partial(move |text| {
+            match text.parse::<i64>() {
+                Ok(value) => value >= min && value <= max,
+                Err(_) => false
+            }
+        }, move |text| integer_prefix_fits(text, min, max))
+    }
+
+    /// A number with an optional minus sign and decimal point, like "-12.5".
+    pub fn decimal() -> Validator {
+        Validator::with_partial(|text| {
+            let text = if text.starts_with("-") {&text[1..]} else {text};
+
+            text != "" && text != "." && is_decimal_prefix(text)
+        }, |text| {
+            is_decimal_prefix(if text.starts_with("-") {&text[1..]} else {text})
+        })
+    }
+
+    /// Text that the regular expression matches from start to end. Partial
+    /// text is accepted as long as some continuation of it could match.
+    pub fn regex(pattern: &str) -> Result<Validator, String> {
+        let regex = regex::Regex::new(&format!("^(?:{})$", pattern))
+            .map_err(|err| err.to_string())?;
+        let partial = regex_prefix_matcher(pattern);
+
+        Ok(Validator::with_partial(move |text| regex.is_match(text), move |text| partial(text)))
+    }
+
+    /// A calendar date written as YYYY-MM-DD.
+    pub fn date() -> Validator {
+        Validator::with_partial(is_date, is_date_prefix)
+    }
+
+    /// An IPv4 address in dotted form, like "192.168.0.1".
+    pub fn ip_address() -> Validator {
+        Validator::with_partial(|text| text.parse::<std::net::Ipv4Addr>().is_ok(), is_ip_address_prefix)
+    }
+
+    pub fn is_valid(&self, text: &str) -> bool {
+        (self.check)(text)
+    }
+
+    /// True if text could still become valid by typing more.
+    pub fn is_valid_prefix(&self, text: &str) -> bool {
+        (self.partial)(text)
+    }
+}
+
+//True if some number in the range starts with the typed text
+fn integer_prefix_fits(text: &str, min: i64, max: i64) -> bool {
+    let (negative, digits) = if text.starts_with("-") {(true, &text[1..])} else {(false, text)};
+
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        return false;
+    }
+
+    if digits.is_empty() {
+        return !negative || min < 0;
+    }
+
+    let prefix = match digits.parse::<i128>() {
+        Ok(prefix) => prefix,
+        Err(_) => return false
+    };
+    let mut low = prefix;
+    let mut high = prefix;
+
+    //Each further digit widens the reachable values tenfold
+    for _ in 0..20 {
+        let (from, to) = if negative {(-high, -low)} else {(low, high)};
+
+        if from <= max as i128 && to >= min as i128 {
+            return true;
+        }
+
+        match (low.checked_mul(10), high.checked_mul(10).and_then(|high| high.checked_add(9))) {
+            (Some(next_low), Some(next_high)) => {
+                low = next_low;
+                high = next_high;
+            },
+            _ => break
+        }
+    }
+
+    false
+}
+
+//Digits with at most one decimal point
+fn is_decimal_prefix(text: &str) -> bool {
+    let mut parts = text.splitn(2, '.');
+    let whole = parts.next().unwrap_or("");
+    let fraction = parts.next().unwrap_or("");
+
+    whole.chars().all(|c| c.is_ascii_digit()) && fraction.chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_date(text: &str) -> bool {
+    let parts: Vec<&str> = text.split('-').collect();
+
+    if parts.len() != 3 || parts[0].len() != 4 || parts[1].len() != 2 || parts[2].len() != 2 {
+        return false;
+    }
+
+    if !parts.iter().all(|part| part.chars().all(|c| c.is_ascii_digit())) {
+        return false;
+    }
+
+    let year: u32 = parts[0].parse().unwrap();
+    let month: u32 = parts[1].parse().unwrap();
+    let day: u32 = parts[2].parse().unwrap();
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 => if leap {29} else {28},
+        _ => return false
+    };
+
+    day >= 1 && day <= days
+}
+
+//The start of YYYY-MM-DD, with month and day in range as far as typed
+fn is_date_prefix(text: &str) -> bool {
+    let bytes = text.as_bytes();
+
+    if bytes.len() > 10 {
+        return false;
+    }
+
+    for (index, &byte) in bytes.iter().enumerate() {
+        let fits = if index == 4 || index == 7 {byte == b'-'} else {byte.is_ascii_digit()};
+
+        if !fits {
+            return false;
+        }
+    }
+
+    if bytes.len() >= 6 && bytes[5] > b'1' {
+        return false;
+    }
+
+    if bytes.len() >= 7 {
+        let month: u32 = text[5..7].parse().unwrap();
+
+        if month < 1 || month > 12 {
+            return false;
+        }
+    }
+
+    if bytes.len() >= 9 && bytes[8] > b'3' {
+        return false;
+    }
+
+    bytes.len() < 10 || is_date(text)
+}
+
+//Up to four groups of at most 255, the last of which may be empty. Like
+//Ipv4Addr, groups may not have leading zeros.
+fn is_ip_address_prefix(text: &str) -> bool {
+    let groups: Vec<&str> = text.split('.').collect();
+
+    if groups.len() > 4 {
+        return false;
+    }
+
+    groups.iter().enumerate().all(|(index, group)| {
+        if group.is_empty() {
+            return index == groups.len() - 1;
+        }
+
+        group.len() <= 3 && group.chars().all(|c| c.is_ascii_digit()) && group.parse::<u32>().unwrap() <= 255 &&
+            !(group.len() > 1 && group.starts_with('0'))
+    })
+}
+
+//Checks whether text can be continued to a full match of pattern, by
+//running it through a DFA and watching for the dead state. Patterns the
+//DFA can not handle accept all partial text.
+fn regex_prefix_matcher(pattern: &str) -> Box<Fn(&str) -> bool> {
+    use regex_automata::dfa::{dense, Automaton};
+    use regex_automata::{Anchored, Input, MatchKind};
+
+    let dfa = dense::Builder::new()
+        .configure(dense::Config::new().match_kind(MatchKind::All))
+        .build(&format!("(?:{})$", pattern));
+
+    match dfa {
+        Ok(dfa) => Box::new(move |text: &str| {
+            let mut state = match dfa.start_state_forward(&Input::new(text).anchored(Anchored::Yes)) {
+                Ok(state) => state,
+                Err(_) => return true
+            };
+
+            for &byte in text.as_bytes() {
+                state = dfa.next_state(state, byte);
+
+                if dfa.is_dead_state(state) {
+                    return false;
+                }
+
+                if dfa.is_quit_state(state) {
+                    return true;
+                }
+            }
+
+            true
+        }),
+        Err(_) => Box::new(|_: &str| true)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum MaskSlot {
+    Digit,
+    Letter,
+    Any,
+    Literal(char)
+}
+
+//Shown in the slots the user has not filled yet
+const MASK_BLANK: char = '_';
+
+/// A fixed layout for the text of an Edit, like "000-000-0000" for a
+/// phone number. In the pattern 0 stands for a digit, L for a letter and
+/// * for any character. Other characters are fixed and the user types
+/// over them. A backslash makes the next character fixed.
+#[derive(Clone, Debug)]
+pub struct InputMask {
+    slots: Vec<MaskSlot>
+}
+
+impl InputMask {
+    pub fn new(pattern: &str) -> InputMask {
+        let mut slots = Vec::new();
+        let mut chars = pattern.chars();
+
+        while let Some(c) = chars.next() {
+            slots.push(match c {
+                '0' => MaskSlot::Digit,
+                'L' => MaskSlot::Letter,
+                '*' => MaskSlot::Any,
+                '\\' => MaskSlot::Literal(chars.next().unwrap_or('\\')),
+                _ => MaskSlot::Literal(c)
+            });
+        }
+
+        InputMask {
+            slots: slots
+        }
+    }
+
+    /// The text before anything is typed, like "___-___-____".
+    pub fn empty_text(&self) -> String {
+        self.slots.iter().map(|slot| match *slot {
+            MaskSlot::Literal(c) => c,
+            _ => MASK_BLANK
+        }).collect()
+    }
+
+    /// True when every slot holds a character it accepts.
+    pub fn is_complete(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+
+        chars.len() == self.slots.len() && self.slots.iter().zip(chars.iter()).all(|(slot, &c)| {
+            match *slot {
+                MaskSlot::Literal(literal) => c == literal,
+                _ => InputMask::accepts(*slot, c)
+            }
+        })
+    }
+
+    /// Types c at position. Returns the new text and caret position, or
+    /// None if c does not fit the next slot. Typing a fixed character
+    /// just moves past it.
+    pub fn insert(&self, text: &str, position: usize, c: char) -> Option<(String, usize)> {
+        let mut chars = self.chars_of(text);
+        let mut position = position;
+
+        while position < self.slots.len() {
+            match self.slots[position] {
+                MaskSlot::Literal(literal) if literal == c => {
+                    return Some((chars.into_iter().collect(), self.skip_literals(position + 1)));
+                },
+                MaskSlot::Literal(_) => position += 1,
+                _ => break
+            }
+        }
+
+        if position >= self.slots.len() || !InputMask::accepts(self.slots[position], c) {
+            return None;
+        }
+
+        chars[position] = c;
+
+        Some((chars.into_iter().collect(), self.skip_literals(position + 1)))
+    }
+
+    /// Types each character of txt that fits, as when pasting. Returns
+    /// the new text and caret position.
+    pub fn insert_str(&self, text: &str, position: usize, txt: &str) -> (String, usize) {
+        let mut result = (self.chars_of(text).into_iter().collect(), position);
+
+        for c in txt.chars() {
+            if let Some(next) = self.insert(&result.0, result.1, c) {
+                result = next;
+            }
+        }
+
+        result
+    }
+
+    /// Clears the slot before position, as Backspace does. Returns the new
+    /// text and caret position.
+    pub fn erase(&self, text: &str, position: usize) -> (String, usize) {
+        let mut chars = self.chars_of(text);
+        let mut position = position.min(self.slots.len());
+
+        while position > 0 {
+            position -= 1;
+
+            if let MaskSlot::Literal(_) = self.slots[position] {
+                continue;
+            }
+
+            chars[position] = MASK_BLANK;
+            break;
+        }
+
+        (chars.into_iter().collect(), position)
+    }
+
+    /// Clears the slots from start up to end, as typing over a selection
+    /// does. Fixed characters stay.
+    pub fn clear_range(&self, text: &str, start: usize, end: usize) -> String {
+        let mut chars = self.chars_of(text);
+
+        for position in start..end.min(self.slots.len()) {
+            if let MaskSlot::Literal(_) = self.slots[position] {
+                continue;
+            }
+
+            chars[position] = MASK_BLANK;
+        }
+
+        chars.into_iter().collect()
+    }
+
+    /// Clears the slot at or after position, as Delete does.
+    pub fn delete(&self, text: &str, position: usize) -> String {
+        let mut chars = self.chars_of(text);
+        let position = self.skip_literals(position);
+
+        if position < self.slots.len() {
+            chars[position] = MASK_BLANK;
+        }
+
+        chars.into_iter().collect()
+    }
+
+    //Text that does not fit the mask is started over
+    fn chars_of(&self, text: &str) -> Vec<char> {
+        let chars: Vec<char> = text.chars().collect();
+
+        if chars.len() == self.slots.len() {
+            chars
+        } else {
+            self.empty_text().chars().collect()
+        }
+    }
+
+    fn skip_literals(&self, position: usize) -> usize {
+        let mut position = position;
+
+        while position < self.slots.len() {
+            if let MaskSlot::Literal(_) = self.slots[position] {
+                position += 1;
+            } else {
+                break;
+            }
+        }
+
+        position
+    }
+
+    //Positions in an Edit count UTF-16 units, so slots take only
+    //characters that fit in one
+    fn accepts(slot: MaskSlot, c: char) -> bool {
+        if c.len_utf16() != 1 {
+            return false;
+        }
+
+        match slot {
+            MaskSlot::Digit => c.is_ascii_digit(),
+            MaskSlot::Letter => c.is_alphabetic(),
+            MaskSlot::Any => !c.is_control(),
+            MaskSlot::Literal(_) => false
+        }
+    }
+}
+
+//Validation attached to an Edit. The Edit is subclassed for as long as
+//this exists.
+struct EditValidation {
+    validator: RefCell<Option<Validator>>,
+    mode: Cell<ValidationMode>,
+    mask: RefCell<Option<InputMask>>
+}
+
+impl EditValidation {
+    fn check(&self, text: &str) -> bool {
+        if let Some(ref mask) = *self.mask.borrow() {
+            if !mask.is_complete(text) {
+                return false;
+            }
+        }
+
+        match *self.validator.borrow() {
+            Some(ref validator) => validator.is_valid(text),
+            None => true
+        }
+    }
+
+    //Check of each keystroke in Reject mode
+    fn check_partial(&self, text: &str) -> bool {
+        match *self.validator.borrow() {
+            Some(ref validator) => validator.is_valid_prefix(text),
+            None => true
+        }
+    }
+
+    //Text the user has not started on is not shown as an error
+    fn is_blank(&self, text: &str) -> bool {
+        match *self.mask.borrow() {
+            Some(ref mask) => text.is_empty() || text == mask.empty_text(),
+            None => text.is_empty()
+        }
+    }
+}
+
+fn get_validation(window: winapi::HWND) -> Option<Rc<EditValidation>> {
+    VALIDATIONS.with(|validations| validations.borrow().get(&(window as usize)).cloned())
+}
+
+fn install_validation(window: winapi::HWND) -> Rc<EditValidation> {
+    if let Some(state) = get_validation(window) {
+        return state;
+    }
+
+    color_invalid_children(unsafe { user32::GetParent(window) });
+
+    //Subclassing through comctl32 keeps working when others subclass the
+    //Edit after us, in any order of removal
+    unsafe {
+        comctl32::SetWindowSubclass(window, Some(validation_proc), 0, 0);
+    }
+
+    let state = Rc::new(EditValidation {
+        validator: RefCell::new(None),
+        mode: Cell::new(ValidationMode::Flag),
+        mask: RefCell::new(None)
+    });
+
+    VALIDATIONS.with(|validations| validations.borrow_mut().insert(window as usize, state.clone()));
+
+    state
+}
+
+fn remove_validation(window: winapi::HWND) {
+    let state = VALIDATIONS.with(|validations| validations.borrow_mut().remove(&(window as usize)));
+
+    if let Some(state) = state {
+        unsafe {
+            comctl32::RemoveWindowSubclass(window, Some(validation_proc), 0);
+        }
+
+        set_prop(window, "cwnd.invalid", ptr::null_mut());
+        window.invalidate();
+    }
+}
+
+fn update_error_state(window: winapi::HWND, state: &EditValidation) {
+    let text = window.get_text();
+    let invalid = !state.is_blank(&text) && !state.check(&text);
+
+    if invalid != !get_prop(window, "cwnd.invalid").is_null() {
+        set_prop(window, "cwnd.invalid", if invalid {1 as winapi::HANDLE} else {ptr::null_mut()});
+        window.invalidate();
+    }
+}
+
+//Handles editing of an Edit with an input mask. Typing, pasting and
+//deleting replace the selection. Returns false for messages the Edit can
+//handle itself.
+fn masked_input(edit: &Edit, state: &EditValidation, message: winapi::UINT, w_param: winapi::WPARAM) -> bool {
+    let EM_UNDO = 0x00C7;
+    let BACKSPACE = 0x08;
+    let CTRL_V = 0x16;
+    let CTRL_X = 0x18;
+    let CTRL_Z = 0x1A;
+
+    let mask = match *state.mask.borrow() {
+        Some(ref mask) => mask.clone(),
+        None => return false
+    };
+    let (start, end) = edit.get_selection();
+    let (start, end) = (start as usize, end as usize);
+
+    let set_text = |text: &str, position: usize| {
+        edit.set_text(text);
+        edit.set_selection(position as i32, position as i32);
+    };
+    //The text with the selection cleared
+    let cleared = || mask.clear_range(&edit.get_text(), start, end);
+    let paste = || {
+        if let Some(txt) = get_clipboard_text(edit.window) {
+            let (text, position) = mask.insert_str(&cleared(), start, &txt);
+
+            set_text(&text, position);
+        }
+    };
+    let cut = || {
+        if start < end {
+            unsafe {
+                user32::SendMessageW(edit.window, winapi::WM_COPY, 0, 0);
+            }
+
+            set_text(&cleared(), start);
+        }
+    };
+
+    match message {
+        winapi::WM_CHAR => {
+            let c = w_param as u32;
+
+            if c == BACKSPACE {
+                if start < end {
+                    set_text(&cleared(), start);
+                } else {
+                    let (text, position) = mask.erase(&edit.get_text(), start);
+
+                    set_text(&text, position);
+                }
+            } else if c == CTRL_V {
+                paste();
+            } else if c == CTRL_X {
+                cut();
+            } else if c == CTRL_Z {
+                //Undoing would break the layout
+            } else if c < 0x20 {
+                return false;
+            } else {
+                match std::char::from_u32(c).and_then(|c| mask.insert(&cleared(), start, c)) {
+                    Some((text, position)) => set_text(&text, position),
+                    None => unsafe {
+                        user32::MessageBeep(0);
+                    }
+                }
+            }
+        },
+        winapi::WM_KEYDOWN if w_param == winapi::VK_DELETE as winapi::WPARAM => {
+            if start < end {
+                set_text(&cleared(), start);
+            } else {
+                set_text(&mask.delete(&edit.get_text(), start), start);
+            }
+        },
+        winapi::WM_PASTE => paste(),
+        winapi::WM_CUT => cut(),
+        winapi::WM_CLEAR => set_text(&cleared(), start),
+        winapi::WM_UNDO => {},
+        _ if message == EM_UNDO => {},
+        _ => return false
+    }
+
+    true
+}
+
+//Colors the invalid Edits of a window that is not a HOWL window, which
+//would not answer WM_CTLCOLOREDIT itself.
+unsafe extern "system" fn color_parent_proc(
+    window: winapi::HWND,
+    message: winapi::UINT,
+    w_param: winapi::WPARAM,
+    l_param: winapi::LPARAM,
+    _id: winapi::UINT_PTR,
+    _data: winapi::DWORD_PTR) -> winapi::LRESULT {
+
+    let WM_CTLCOLOREDIT = 0x0133;
+    let WM_CTLCOLORSTATIC = 0x0138;
+
+    if (message == WM_CTLCOLOREDIT || message == WM_CTLCOLORSTATIC) &&
+        !get_prop(l_param as winapi::HWND, "cwnd.invalid").is_null() {
+
+        if let Some(brush) = control_color(window, message, w_param as winapi::HDC, l_param as winapi::HWND) {
+            return brush as winapi::LRESULT;
+        }
+    }
+
+    if message == winapi::WM_NCDESTROY {
+        comctl32::RemoveWindowSubclass(window, Some(color_parent_proc), 0);
+    }
+
+    comctl32::DefSubclassProc(window, message, w_param, l_param)
+}
+
+fn color_invalid_children(parent: winapi::HWND) {
+    if parent.is_null() || get_class_name(parent) == "HOWL" {
+        return;
+    }
+
+    //Subclassing again with the same procedure and id adds nothing
+    unsafe {
+        comctl32::SetWindowSubclass(parent, Some(color_parent_proc), 0, 0);
+    }
+}
+
+fn get_clipboard_text(window: winapi::HWND) -> Option<String> {
+    let CF_UNICODETEXT = 13;
+    let mut text = None;
+
+    unsafe {
+        if user32::OpenClipboard(window) == 0 {
+            return None;
+        }
+
+        let data = user32::GetClipboardData(CF_UNICODETEXT);
+
+        if !data.is_null() {
+            let chars = kernel32::GlobalLock(data) as *const u16;
+
+            if !chars.is_null() {
+                let mut len = 0;
+
+                while *chars.offset(len) != 0 {
+                    len += 1;
+                }
+
+                text = Some(String::from_utf16_lossy(std::slice::from_raw_parts(chars, len as usize)));
+                kernel32::GlobalUnlock(data);
+            }
+        }
+
+        user32::CloseClipboard();
+    }
+
+    text
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SearchDirection {
+    Forward,
+    Backward
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FindOptions {
+    pub match_case: bool,
+    /// Only match where the pattern is not part of a longer word.
+    pub whole_word: bool,
+    /// Treat the pattern as a regular expression. The replacement can then
+    /// refer to groups as $1 or ${name}.
+    pub regex: bool,
+    pub direction: SearchDirection,
+    /// Continue from the other end of the text when nothing is found.
+    pub wrap_around: bool
+}
+
+impl FindOptions {
+    pub fn new() -> FindOptions {
+        FindOptions {
+            match_case: false,
+            whole_word: false,
+            regex: false,
+            direction: SearchDirection::Forward,
+            wrap_around: true
+        }
+    }
+}
+
+fn build_search_regex(pattern: &str, options: &FindOptions) -> Result<regex::Regex, String> {
+    let mut source = if options.regex {
+        format!("(?:{})", pattern)
+    } else {
+        regex::escape(pattern)
+    };
+
+    if options.whole_word {
+        source = format!(r"\b{}\b", source);
+    }
+
+    regex::RegexBuilder::new(&source)
+        .case_insensitive(!options.match_case)
+        .build()
+        .map_err(|err| err.to_string())
+}
+
+//Converts a position counted in UTF-16 units, as an Edit counts, to a
+//byte index into the string.
+fn utf16_to_byte(text: &str, position: usize) -> usize {
+    let mut units = 0;
+
+    for (index, c) in text.char_indices() {
+        if units >= position {
+            return index;
+        }
+
+        units += c.len_utf16();
+    }
+
+    text.len()
+}
+
+fn byte_to_utf16(text: &str, index: usize) -> usize {
+    text[..index].encode_utf16().count()
+}
+
+//First non-empty match starting at or after the byte index
+fn next_match(regex: &regex::Regex, text: &str, from: usize) -> Option<(usize, usize)> {
+    let mut start = from;
+
+    while start <= text.len() {
+        match regex.find_at(text, start) {
+            Some(found) if found.start() == found.end() => {
+                start = found.end() + text[found.end()..].chars().next().map_or(1, |c| c.len_utf8());
+            },
+            Some(found) => return Some((found.start(), found.end())),
+            None => return None
+        }
+    }
+
+    None
+}
+
+//Last non-empty match ending at or before the byte index
+fn last_match(regex: &regex::Regex, text: &str, limit: usize) -> Option<(usize, usize)> {
+    let mut last = None;
+    let mut start = 0;
+
+    while let Some(found) = next_match(regex, text, start) {
+        if found.1 > limit {
+            break;
+        }
+
+        last = Some(found);
+        start = found.1;
+    }
+
+    last
+}
+
+/// Searches text for pattern, forward from position from or backward up
+/// to it. Positions count UTF-16 units, the way Edit::get_selection()
+/// does. Returns the start and end of the match, or an error if the
+/// regular expression is invalid.
+pub fn find_text(text: &str, pattern: &str, from: usize, options: &FindOptions) -> Result<Option<(usize, usize)>, String> {
+    if pattern.is_empty() {
+        return Ok(None);
+    }
+
+    let regex = build_search_regex(pattern, options)?;
+    let from = utf16_to_byte(text, from);
+    let mut found = match options.direction {
+        SearchDirection::Forward => next_match(&regex, text, from),
+        SearchDirection::Backward => last_match(&regex, text, from)
+    };
+
+    if found.is_none() && options.wrap_around {
+        found = match options.direction {
+            SearchDirection::Forward => next_match(&regex, text, 0),
+            SearchDirection::Backward => last_match(&regex, text, text.len())
+        };
+    }
+
+    Ok(found.map(|(start, end)| (byte_to_utf16(text, start), byte_to_utf16(text, end))))
+}
+
+/// If the range of text from start to end is exactly a match of pattern,
+/// returns what it should be replaced with. Positions count UTF-16 units.
+pub fn replace_match(text: &str, pattern: &str, replacement: &str, start: usize, end: usize, options: &FindOptions) -> Result<Option<String>, String> {
+    if pattern.is_empty() {
+        return Ok(None);
+    }
+
+    let regex = build_search_regex(pattern, options)?;
+    let start = utf16_to_byte(text, start);
+    let end = utf16_to_byte(text, end);
+
+    if next_match(&regex, text, start) != Some((start, end)) {
+        return Ok(None);
+    }
+
+    if !options.regex {
+        return Ok(Some(String::from(replacement)));
+    }
+
+    let captures = match regex.captures_at(text, start) {
+        Some(captures) => captures,
+        None => return Ok(None)
+    };
+
+    if captures.get(0).map(|found| (found.start(), found.end())) != Some((start, end)) {
+        return Ok(None);
+    }
+
+    let mut expanded = String::new();
+
+    captures.expand(replacement, &mut expanded);
+
+    Ok(Some(expanded))
+}
+
+/// Replaces every match of pattern in text, ignoring the direction.
+/// Empty matches are left alone, as find_text() skips them. Returns the
+/// new text and the number of replacements.
+pub fn replace_all_text(text: &str, pattern: &str, replacement: &str, options: &FindOptions) -> Result<(String, usize), String> {
+    if pattern.is_empty() {
+        return Ok((String::from(text), 0));
+    }
+
+    let regex = build_search_regex(pattern, options)?;
+    let mut result = String::new();
+    let mut count = 0;
+    let mut copied = 0;
+
+    for captures in regex.captures_iter(text) {
+        let found = captures.get(0).unwrap();
+
+        if found.start() == found.end() {
+            continue;
+        }
+
+        result.push_str(&text[copied..found.start()]);
+
+        if options.regex {
+            captures.expand(replacement, &mut result);
+        } else {
+            result.push_str(replacement);
+        }
+
+        copied = found.end();
+        count += 1;
+    }
+
+    result.push_str(&text[copied..]);
+
+    Ok((result, count))
+}
+
+//Ids of the controls in a FindReplaceDialog. Enter and Escape send IDOK
+//and IDCANCEL, which are Find Next and Close.
+const FIND_NEXT_ID: u16 = 1;
+const FIND_CLOSE_ID: u16 = 2;
+const REPLACE_ID: u16 = 3;
+const REPLACE_ALL_ID: u16 = 4;
+const MATCH_CASE_ID: u16 = 5;
+const WHOLE_WORD_ID: u16 = 6;
+const USE_REGEX_ID: u16 = 7;
+const SEARCH_UP_ID: u16 = 8;
+const WRAP_AROUND_ID: u16 = 9;
+
+//Event handler and controls of a FindReplaceDialog.
+struct FindDialogState {
+    window: winapi::HWND,
+    target: winapi::HWND,
+    find_edit: Edit,
+    replace_edit: Edit,
+    match_case: Checkbox,
+    whole_word: Checkbox,
+    use_regex: Checkbox,
+    search_up: Checkbox,
+    wrap_around: Checkbox,
+    status: winapi::HWND
+}
+
+impl FindDialogState {
+    fn target(&self) -> Option<Edit> {
+        unsafe {
+            if user32::IsWindow(self.target) == 0 {
+                return None;
+            }
+        }
+
+        Some(Edit {
+            window: self.target
+        })
+    }
+
+    fn get_options(&self) -> FindOptions {
+        FindOptions {
+            match_case: self.match_case.is_checked(),
+            whole_word: self.whole_word.is_checked(),
+            regex: self.use_regex.is_checked(),
+            direction: if self.search_up.is_checked() {SearchDirection::Backward} else {SearchDirection::Forward},
+            wrap_around: self.wrap_around.is_checked()
+        }
+    }
+
+    fn set_options(&self, options: &FindOptions) {
+        self.match_case.set_checked(options.match_case);
+        self.whole_word.set_checked(options.whole_word);
+        self.use_regex.set_checked(options.regex);
+        self.search_up.set_checked(options.direction == SearchDirection::Backward);
+        self.wrap_around.set_checked(options.wrap_around);
+    }
+
+    //An invalid pattern is reported in the status line
+    fn report(&self, result: Result<String, String>) {
+        match result {
+            Ok(status) => self.status.set_text(&status),
+            Err(_) => self.status.set_text("Invalid regular expression.")
+        }
+    }
+
+    fn find_next(&self) -> bool {
+        let target = match self.target() {
+            Some(target) => target,
+            None => return false
+        };
+        let options = self.get_options();
+        let (start, end) = target.get_selection();
+        let from = if options.direction == SearchDirection::Backward {start} else {end};
+
+        match find_text(&target.get_text(), &self.find_edit.get_text(), from as usize, &options) {
+            Ok(Some((start, end))) => {
+                target.set_selection(start as i32, end as i32);
+                target.scroll_to_caret();
+                self.report(Ok(String::new()));
+
+                true
+            },
+            Ok(None) => {
+                unsafe {
+                    user32::MessageBeep(0);
+                }
+
+                self.report(Ok(String::from("Text not found.")));
+
+                false
+            },
+            Err(err) => {
+                self.report(Err(err));
+
+                false
+            }
+        }
+    }
+
+    fn replace(&self) -> bool {
+        let target = match self.target() {
+            Some(target) => target,
+            None => return false
+        };
+        let options = self.get_options();
+        let (start, end) = target.get_selection();
+
+        match replace_match(&target.get_text(), &self.find_edit.get_text(), &self.replace_edit.get_text(), start as usize, end as usize, &options) {
+            Ok(Some(replacement)) => {
+                target.replace_selection(&replacement);
+
+                //Search up from where the replaced text began
+                if options.direction == SearchDirection::Backward {
+                    target.set_selection(start, start);
+                }
+            },
+            Ok(None) => {},
+            Err(err) => {
+                self.report(Err(err));
+
+                return false;
+            }
+        }
+
+        self.find_next()
+    }
+
+    fn replace_all(&self) -> usize {
+        let target = match self.target() {
+            Some(target) => target,
+            None => return 0
+        };
+
+        match replace_all_text(&target.get_text(), &self.find_edit.get_text(), &self.replace_edit.get_text(), &self.get_options()) {
+            Ok((text, count)) => {
+                if count > 0 {
+                    let EM_GETLIMITTEXT = 0x00D5;
+                    let EM_SETLIMITTEXT = 0x00C5;
+                    let length = text.encode_utf16().count();
+
+                    unsafe {
+                        let limit = user32::SendMessageW(target.window, EM_GETLIMITTEXT, 0, 0) as usize;
+
+                        //Replacing the selection keeps the change undoable,
+                        //but it is cut at the text limit, so lift it briefly
+                        if length > limit {
+                            user32::SendMessageW(target.window, EM_SETLIMITTEXT, length, 0);
+                        }
+
+                        target.set_selection(0, -1);
+                        target.replace_selection(&text);
+
+                        if length > limit {
+                            user32::SendMessageW(target.window, EM_SETLIMITTEXT, limit, 0);
+                        }
+                    }
+                }
+
+                self.report(Ok(format!("Replaced {} occurrence(s).", count)));
+
+                count
+            },
+            Err(err) => {
+                self.report(Err(err));
+
+                0
+            }
+        }
+    }
+}
+
+impl WindowEventHandler for FindDialogState {
+    fn on_command(&mut self, source_id: u16, command_type: u16) {
+        let BN_CLICKED = 0;
+
+        if command_type != BN_CLICKED {
+            return;
+        }
+
+        match source_id {
+            FIND_NEXT_ID => {
+                self.find_next();
+            },
+            REPLACE_ID => {
+                self.replace();
+            },
+            REPLACE_ALL_ID => {
+                self.replace_all();
+            },
+            FIND_CLOSE_ID => self.window.hide(),
+            _ => {
+                let checkbox = match source_id {
+                    MATCH_CASE_ID => &self.match_case,
+                    WHOLE_WORD_ID => &self.whole_word,
+                    USE_REGEX_ID => &self.use_regex,
+                    SEARCH_UP_ID => &self.search_up,
+                    WRAP_AROUND_ID => &self.wrap_around,
+                    _ => return
+                };
+
+                checkbox.set_checked(!checkbox.is_checked());
+            }
+        }
+    }
+
+    fn on_size(&mut self, width: i32, height: i32) {
+    }
+
+    fn on_move(&mut self, x: i32, y: i32) {
+    }
+
+    //Closing only hides the dialog so it can be shown again
+    fn on_close(&mut self) {
+        self.window.hide();
+    }
+
+    fn on_destroy(&mut self) {
+    }
+}
+
+/// A modeless dialog that finds and replaces text in a multi-line Edit.
+/// The found text is selected in the Edit, so create the Edit with
+/// EditOptions::keep_selection for it to stay visible. Call show() from
+/// the application's Ctrl+F handler and find_next() for F3. Within the
+/// dialog, Tab moves between the controls, Enter finds the next match
+/// and Escape closes it, as long as Application::main_loop() is used.
+pub struct FindReplaceDialog {
+    window : winapi::HWND,
+    state : Box<FindDialogState>
+}
+
+impl Window for FindReplaceDialog {
+    fn get_hwnd(&self) -> winapi::HWND {
+		return self.window;
+	}
+}
+
+impl FindReplaceDialog {
+    pub fn new(target: &Edit) -> FindReplaceDialog {
+        let GA_ROOT = 2;
+        let owner = unsafe {
+            user32::GetAncestor(target.get_hwnd(), GA_ROOT)
+        };
+        let wnd = WindowBuilder::new()
+            .title("Find and Replace")
+            .class_name("HOWL")
+            .parent(owner)
+            .style(winapi::WS_POPUP | winapi::WS_CAPTION | winapi::WS_SYSMENU)
+            .size(390, 200)
+            .create();
+        let label = |title: &str, y: i32| {
+            WindowBuilder::new()
+                .title(title)
+                .class_name("STATIC")
+                .style(winapi::WS_VISIBLE | winapi::WS_CHILD)
+                .position(10, y + 3)
+                .size(85, 20)
+                .parent(wnd)
+                .create();
+        };
+        let mut edit_options = EditOptions::new();
+
+        edit_options.horizontal_scroll = true;
+
+        label("Find what:", 10);
+        label("Replace with:", 40);
+
+        let state = Box::new(FindDialogState {
+            window: wnd,
+            target: target.get_hwnd(),
+            find_edit: Edit::with_options(&wnd, 0, 95, 10, 180, 22, &edit_options),
+            replace_edit: Edit::with_options(&wnd, 0, 95, 40, 180, 22, &edit_options),
+            match_case: Checkbox::new(&wnd, MATCH_CASE_ID, "Match case", 10, 72, 125, 20),
+            whole_word: Checkbox::new(&wnd, WHOLE_WORD_ID, "Whole word", 140, 72, 125, 20),
+            use_regex: Checkbox::new(&wnd, USE_REGEX_ID, "Regular expression", 10, 96, 125, 20),
+            search_up: Checkbox::new(&wnd, SEARCH_UP_ID, "Search up", 140, 96, 125, 20),
+            wrap_around: Checkbox::new(&wnd, WRAP_AROUND_ID, "Wrap around", 10, 120, 125, 20),
+            status: WindowBuilder::new()
+                .class_name("STATIC")
+                .style(winapi::WS_VISIBLE | winapi::WS_CHILD)
+                .position(140, 123)
+                .size(140, 20)
+                .parent(wnd)
+                .create()
+        });
+
+        Button::new(&wnd, FIND_NEXT_ID, "Find Next", 285, 10, 85, 24);
+        Button::new(&wnd, REPLACE_ID, "Replace", 285, 40, 85, 24);
+        Button::new(&wnd, REPLACE_ALL_ID, "Replace All", 285, 70, 85, 24);
+        Button::new(&wnd, FIND_CLOSE_ID, "Close", 285, 100, 85, 24);
+
+        //Edits are not tab stops by default
+        for edit in &[&state.find_edit, &state.replace_edit] {
+            unsafe {
+                let style = user32::GetWindowLongW(edit.get_hwnd(), winapi::GWL_STYLE) as winapi::DWORD;
+
+                user32::SetWindowLongW(edit.get_hwnd(), winapi::GWL_STYLE, (style | winapi::WS_TABSTOP) as winapi::LONG);
+            }
+        }
+
+        state.set_options(&FindOptions::new());
+        set_prop(wnd, "cwnd.dialog", 1 as winapi::HANDLE);
+        wnd.attach_event_handler(&*state);
+
+        FindReplaceDialog {
+            window: wnd,
+            state: state
+        }
+    }
+
+    /// Shows the dialog with the focus in the search field. A selection
+    /// within one line of the Edit becomes the search text.
+    pub fn show(&self) {
+        if let Some(target) = self.state.target() {
+            let (start, end) = target.get_selection();
+            let text: Vec<u16> = target.get_text().encode_utf16().collect();
+
+            if start < end && (end as usize) <= text.len() {
+                let selected = String::from_utf16_lossy(&text[start as usize..end as usize]);
+
+                if !selected.contains('\n') {
+                    self.state.find_edit.set_text(&selected);
+                }
+            }
+        }
+
+        self.window.show();
+
+        unsafe {
+            user32::SetFocus(self.state.find_edit.get_hwnd());
+        }
+
+        self.state.find_edit.set_selection(0, -1);
+    }
+
+    pub fn set_search_text(&self, txt: &str) {
+        self.state.find_edit.set_text(txt);
+    }
+
+    pub fn get_search_text(&self) -> String {
+        self.state.find_edit.get_text()
+    }
+
+    pub fn set_replace_text(&self, txt: &str) {
+        self.state.replace_edit.set_text(txt);
+    }
+
+    pub fn get_replace_text(&self) -> String {
+        self.state.replace_edit.get_text()
+    }
+
+    pub fn set_options(&self, options: &FindOptions) {
+        self.state.set_options(options);
+    }
+
+    pub fn get_options(&self) -> FindOptions {
+        self.state.get_options()
+    }
+
+    /// Selects the next match in the Edit. Returns false if there is none.
+    pub fn find_next(&self) -> bool {
+        self.state.find_next()
+    }
+
+    /// Replaces the selection if it is a match, then finds the next one.
+    pub fn replace(&self) -> bool {
+        self.state.replace()
+    }
+
+    /// Replaces every match and returns how many there were.
+    pub fn replace_all(&self) -> usize {
+        self.state.replace_all()
+    }
+}
+
+impl Drop for FindReplaceDialog {
+    fn drop(&mut self) {
+        //The handler is about to be freed
+        let mut window = self.window;
+
+        window.detach_event_handler();
+        set_prop(self.window, "cwnd.dialog", ptr::null_mut());
+
+        unsafe {
+            user32::DestroyWindow(self.window);
+        }
+    }
+}
+
+//The rich edit window class lives in this library
+static LOAD_RICH_EDIT: Once = Once::new();
+
+//CHARFORMATW from Richedit.h
+#[repr(C)]
+struct CharFormat {
+    cbSize: winapi::UINT,
+    dwMask: winapi::DWORD,
+    dwEffects: winapi::DWORD,
+    yHeight: winapi::LONG,
+    yOffset: winapi::LONG,
+    crTextColor: winapi::COLORREF,
+    bCharSet: winapi::BYTE,
+    bPitchAndFamily: winapi::BYTE,
+    szFaceName: [winapi::WCHAR; 32]
+}
+
+#[repr(C)]
+struct CharRange {
+    cpMin: winapi::LONG,
+    cpMax: winapi::LONG
+}
+
+//Richedit.h packs EDITSTREAM to 4 bytes, which matters on 64 bit
+#[repr(C, packed(4))]
+struct EditStream {
+    dwCookie: winapi::DWORD_PTR,
+    dwError: winapi::DWORD,
+    pfnCallback: unsafe extern "system" fn(winapi::DWORD_PTR, *mut u8, winapi::LONG, *mut winapi::LONG) -> winapi::DWORD
+}
+
+//Data read by EM_STREAMIN
+struct StreamSource {
+    data: Vec<u8>,
+    position: usize
+}
+
+unsafe extern "system" fn stream_out_proc(cookie: winapi::DWORD_PTR, buffer: *mut u8, size: winapi::LONG, written: *mut winapi::LONG) -> winapi::DWORD {
+    let output = &mut *(cookie as *mut Vec<u8>);
+
+    output.extend_from_slice(std::slice::from_raw_parts(buffer, size as usize));
+    *written = size;
+
+    0
+}
+
+unsafe extern "system" fn stream_in_proc(cookie: winapi::DWORD_PTR, buffer: *mut u8, size: winapi::LONG, read: *mut winapi::LONG) -> winapi::DWORD {
+    let source = &mut *(cookie as *mut StreamSource);
+    let count = (source.data.len() - source.position).min(size as usize);
+
+    ptr::copy_nonoverlapping(source.data[source.position..].as_ptr(), buffer, count);
+    source.position += count;
+    *read = count as winapi::LONG;
+
+    0
+}
+
+/// Character formatting of a span of RichEdit text.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TextStyle {
+    /// None uses the default text color.
+    pub color: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+    /// In points. None uses the default size.
+    pub size: Option<i32>
+}
+
+impl TextStyle {
+    pub fn new() -> TextStyle {
+        TextStyle {
+            color: None,
+            bold: false,
+            italic: false,
+            size: None
+        }
+    }
+}
+
+/// A multi-line text control where each span of text can have its own
+/// color, weight, slant and size. Positions count characters with a
+/// line break counting as one, unlike Edit.
+pub struct RichEdit {
+    window : winapi::HWND
+}
+
+impl Window for RichEdit {
+    fn get_hwnd(&self) -> winapi::HWND {
+		return self.window;
+	}
+}
+
+impl RichEdit {
+    pub fn new(parent: &Window, id: u16, x: i32, y: i32, width: i32, height: i32) -> RichEdit {
+        let ES_MULTILINE = 0x0004;
+        let ES_AUTOVSCROLL = 0x0040;
+        let ES_NOHIDESEL = 0x0100;
+        let ES_WANTRETURN = 0x1000;
+        let EM_SETEVENTMASK = 0x0445;
+        let ENM_CHANGE = 0x0001;
+
+        LOAD_RICH_EDIT.call_once(|| unsafe {
+            kernel32::LoadLibraryW(to_wchar("Msftedit.dll").as_ptr());
+        });
+
+        let wnd = WindowBuilder::new()
+            .class_name("RICHEDIT50W")
+            .style(winapi::WS_CHILD | winapi::WS_VISIBLE | winapi::WS_VSCROLL | winapi::WS_TABSTOP |
+                ES_MULTILINE | ES_AUTOVSCROLL | ES_NOHIDESEL | ES_WANTRETURN)
+            .extra_style(winapi::WS_EX_CLIENTEDGE)
+            .position(x, y)
+            .size(width, height)
+            .parent(parent.get_hwnd())
+            .id(id)
+            .create();
+
+        //Rich edit controls only report changes when asked to
+        unsafe {
+            user32::SendMessageW(wnd, EM_SETEVENTMASK, 0, ENM_CHANGE);
+        }
+
+        RichEdit {
+            window: wnd
+        }
+    }
+
+    pub fn set_read_only(&self, read_only : bool) {
+        let EM_SETREADONLY = 0x00CF;
+
+        unsafe {
+            user32::SendMessageW(self.window, EM_SETREADONLY, if read_only {1} else {0}, 0);
+        }
+    }
+
+    /// Returns the number of characters, counting a line break as one.
+    pub fn get_length(&self) -> i32 {
+        let EM_GETTEXTLENGTHEX = 0x045F;
+        let GTL_PRECISE = 2;
+        let GTL_NUMCHARS = 8;
+        let CP_UNICODE = 1200;
+        let length: [winapi::DWORD; 2] = [GTL_PRECISE | GTL_NUMCHARS, CP_UNICODE];
+
+        unsafe {
+            return user32::SendMessageW(self.window, EM_GETTEXTLENGTHEX, length.as_ptr() as winapi::WPARAM, 0) as i32;
+        }
+    }
+
+    pub fn get_selection(&self) -> (i32, i32) {
+        let EM_EXGETSEL = 0x0434;
+        let mut range = CharRange {
+            cpMin: 0,
+            cpMax: 0
+        };
+
+        unsafe {
+            user32::SendMessageW(self.window, EM_EXGETSEL, 0, &mut range as *mut CharRange as winapi::LPARAM);
+        }
+
+        (range.cpMin, range.cpMax)
+    }
+
+    /// Selects the text from start up to end. Pass 0 and -1 to select
+    /// everything.
+    pub fn set_selection(&self, start: i32, end: i32) {
+        let EM_EXSETSEL = 0x0437;
+        let range = CharRange {
+            cpMin: start,
+            cpMax: end
+        };
+
+        unsafe {
+            user32::SendMessageW(self.window, EM_EXSETSEL, 0, &range as *const CharRange as winapi::LPARAM);
+        }
+    }
+
+    /// Adds text at the end in the default style.
+    pub fn append_text(&self, txt: &str) {
+        self.append_styled(txt, &TextStyle::new());
+    }
+
+    /// Adds text at the end in the given style.
+    pub fn append_styled(&self, txt: &str, style: &TextStyle) {
+        let EM_REPLACESEL = 0x00C2;
+        let start = self.get_length();
+        let txt = to_wchar(txt);
+
+        self.set_selection(start, start);
+
+        unsafe {
+            user32::SendMessageW(self.window, EM_REPLACESEL, 0, txt.as_ptr() as winapi::LPARAM);
+        }
+
+        let end = self.get_length();
+
+        self.set_style(start, end, style);
+        self.set_selection(end, end);
+    }
+
+    /// Changes the style of the text from start up to end. Style values
+    /// left at None fall back to the defaults.
+    pub fn set_style(&self, start: i32, end: i32, style: &TextStyle) {
+        let EM_SETCHARFORMAT = 0x0444;
+        let SCF_SELECTION = 0x0001;
+        let CFM_BOLD = 0x0001;
+        let CFM_ITALIC = 0x0002;
+        let CFM_SIZE = 0x8000_0000;
+        let CFM_COLOR = 0x4000_0000;
+        let CFE_BOLD = 0x0001;
+        let CFE_ITALIC = 0x0002;
+        let CFE_AUTOCOLOR = 0x4000_0000;
+
+        let (old_start, old_end) = self.get_selection();
+        let mut format = self.get_char_format(false);
+
+        format.dwMask = CFM_BOLD | CFM_ITALIC | CFM_SIZE | CFM_COLOR;
+        format.dwEffects = 0;
+
+        if style.bold {
+            format.dwEffects |= CFE_BOLD;
+        }
+
+        if style.italic {
+            format.dwEffects |= CFE_ITALIC;
+        }
+
+        match style.color {
+            Some(color) => format.crTextColor = color.to_colorref(),
+            None => format.dwEffects |= CFE_AUTOCOLOR
+        }
+
+        //Sizes are in twips
+        if let Some(size) = style.size {
+            format.yHeight = size * 20;
+        }
+
+        self.set_selection(start, end);
+
+        unsafe {
+            user32::SendMessageW(self.window, EM_SETCHARFORMAT, SCF_SELECTION, &format as *const CharFormat as winapi::LPARAM);
+        }
+
+        self.set_selection(old_start, old_end);
+    }
+
+    /// Returns the style of the character at position.
+    pub fn get_style_at(&self, position: i32) -> TextStyle {
+        let CFE_BOLD = 0x0001;
+        let CFE_ITALIC = 0x0002;
+        let CFE_AUTOCOLOR = 0x4000_0000;
+
+        let (old_start, old_end) = self.get_selection();
+
+        self.set_selection(position, position + 1);
+
+        let format = self.get_char_format(true);
+        let default_height = self.get_char_format(false).yHeight;
+
+        self.set_selection(old_start, old_end);
+
+        TextStyle {
+            color: if format.dwEffects & CFE_AUTOCOLOR != 0 {None} else {Some(Color::from_colorref(format.crTextColor))},
+            bold: format.dwEffects & CFE_BOLD != 0,
+            italic: format.dwEffects & CFE_ITALIC != 0,
+            size: if format.yHeight == default_height {None} else {Some(format.yHeight / 20)}
+        }
+    }
+
+    //Format of the selection, or the control's default format
+    fn get_char_format(&self, selection: bool) -> CharFormat {
+        let EM_GETCHARFORMAT = 0x043A;
+        let mut format = CharFormat {
+            cbSize: mem::size_of::<CharFormat>() as winapi::UINT,
+            dwMask: 0,
+            dwEffects: 0,
+            yHeight: 0,
+            yOffset: 0,
+            crTextColor: 0,
+            bCharSet: 0,
+            bPitchAndFamily: 0,
+            szFaceName: [0; 32]
+        };
+
+        unsafe {
+            user32::SendMessageW(self.window, EM_GETCHARFORMAT, if selection {1} else {0}, &mut format as *mut CharFormat as winapi::LPARAM);
+        }
+
+        format
+    }
+
+    /// Returns the text and its styles as RTF.
+    pub fn get_rtf(&self) -> String {
+        let EM_STREAMOUT = 0x044A;
+        let SF_RTF = 0x0002;
+        let mut output: Vec<u8> = Vec::new();
+        let mut stream = EditStream {
+            dwCookie: &mut output as *mut Vec<u8> as winapi::DWORD_PTR,
+            dwError: 0,
+            pfnCallback: stream_out_proc
+        };
+
+        unsafe {
+            user32::SendMessageW(self.window, EM_STREAMOUT, SF_RTF, &mut stream as *mut EditStream as winapi::LPARAM);
+        }
+
+        //RTF escapes anything outside of 7 bit ASCII
+        String::from_utf8_lossy(&output).into_owned()
+    }
+
+    /// Replaces the content with an RTF document. Text that does not start
+    /// with an RTF header is rejected and the content is left alone.
+    pub fn set_rtf(&self, rtf: &str) -> Result<(), String> {
+        let EM_STREAMIN = 0x0449;
+        let SF_RTF = 0x0002;
+
+        //The control would take anything else as plain text
+        if !rtf.trim_start().starts_with("{\\rtf") {
+            return Err(String::from("Invalid RTF."));
+        }
+
+        let mut source = StreamSource {
+            data: rtf.as_bytes().to_vec(),
+            position: 0
+        };
+        let mut stream = EditStream {
+            dwCookie: &mut source as *mut StreamSource as winapi::DWORD_PTR,
+            dwError: 0,
+            pfnCallback: stream_in_proc
+        };
+
+        unsafe {
+            user32::SendMessageW(self.window, EM_STREAMIN, SF_RTF, &mut stream as *mut EditStream as winapi::LPARAM);
+        }
+
+        //An empty document reads no characters, so only the error tells
+        if stream.dwError != 0 {
+            return Err(String::from("Invalid RTF."));
+        }
+
+        Ok(())
+    }
+}
+
+pub struct ListBox {
+    window : winapi::HWND
+}
+
+impl Window for ListBox {
+    fn get_hwnd(&self) -> winapi::HWND {
+		return self.window;
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SelectionMode {
+    /// At most one item is selected.
+    Single,
+    /// Clicking an item toggles its selection.
+    Multiple,
+    /// Shift and Ctrl clicks extend the selection.
+    Extended
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ListBoxOptions {
+    pub selection: SelectionMode,
+    /// Keep items sorted alphabetically. insert_item() places items in
+    /// sorted order instead of at the given index.
+    pub sorted: bool
+}
+
+impl ListBoxOptions {
+    pub fn new() -> ListBoxOptions {
+        ListBoxOptions {
+            selection: SelectionMode::Single,
+            sorted: false
+        }
+    }
+}
+
+impl ListBox {
+    pub fn new(parent: &Window, id: u16, x: i32, y: i32, width: i32, height: i32) -> ListBox {
+        ListBox::with_options(parent, id, x, y, width, height, &ListBoxOptions::new())
+    }
+
+    pub fn with_options(parent: &Window, id: u16, x: i32, y: i32, width: i32, height: i32, options: &ListBoxOptions) -> ListBox {
+        let LBS_NOTIFY = 1;
+        let LBS_SORT = 2;
+        let LBS_MULTIPLESEL = 8;
+        let LBS_HASSTRINGS = 64;
+        let LBS_EXTENDEDSEL = 0x0800;
+
+        let mut style = winapi::WS_VISIBLE | winapi::WS_VSCROLL | winapi::WS_CHILD | LBS_NOTIFY | LBS_HASSTRINGS;
+
+        style = style | match options.selection {
+            SelectionMode::Single => 0,
+            SelectionMode::Multiple => LBS_MULTIPLESEL,
+            SelectionMode::Extended => LBS_EXTENDEDSEL
+        };
+
+        if options.sorted {
+            style = style | LBS_SORT;
+        }
+
+        let wnd = WindowBuilder::new()
+            .class_name("LISTBOX")
+            .style(style)
+            .position(x, y)
+            .size(width, height)
+            .parent(parent.get_hwnd())
+            .id(id)
+            .create();
+        ListBox {
+            window: wnd
+        }
+    }
+
+    fn is_sorted(&self) -> bool {
+        let LBS_SORT = 2;
+
+        unsafe {
+            return user32::GetWindowLongW(self.window, winapi::GWL_STYLE) as winapi::DWORD & LBS_SORT != 0;
+        }
+    }
+
+    /// Inserts the item before idx, or appends it if idx is past the end.
+    /// Sorted list boxes ignore idx. Returns the index of the new item.
+    pub fn insert_item(&self, idx : u32, val : &str) -> i32 {
+        let val = to_wchar(val);
+        let LB_ADDSTRING = 384;
+        let LB_INSERTSTRING = 385;
+
+        unsafe {
+            if self.is_sorted() {
+                return user32::SendMessageW(self.window, LB_ADDSTRING, 0, val.as_ptr() as winapi::LPARAM) as i32;
+            }
+
+            let idx = if idx as i32 >= self.get_item_count() {-1} else {idx as i32};
+
+            return user32::SendMessageW(self.window, LB_INSERTSTRING, idx as winapi::WPARAM, val.as_ptr() as winapi::LPARAM) as i32;
+        }
+    }
+
+    pub fn get_item_text(&self, idx : u32) -> String {
+        let LB_GETTEXT = 393;
+        let LB_GETTEXTLEN = 394;
+
+        unsafe {
+            let len = user32::SendMessageW(self.window, LB_GETTEXTLEN, idx as winapi::WPARAM, 0);
+
+            if len < 0 {
+                return String::new();
+            }
+
+            let mut v : Vec<u16> = vec![0; len as usize + 1];
+
+            user32::SendMessageW(self.window, LB_GETTEXT, idx as winapi::WPARAM, v.as_mut_ptr() as winapi::LPARAM);
+            v.truncate(len as usize);
+
+            return String::from_utf16_lossy(&v[..]);
+        }
+    }
+
+    /// Replaces the text of an item. Its selection state and item data are
+    /// kept. In a sorted list box the item may move; the new index is
+    /// returned. Returns -1 if there is no such item, or a negative value if
+    /// the new text could not be added, in which case the item is removed.
+    pub fn set_item_text(&self, idx : u32, val : &str) -> i32 {
+        let LB_GETITEMDATA = 409;
+        let LB_SETITEMDATA = 410;
+        let LB_DELETESTRING = 386;
+        let LB_ERR = -1;
+
+        let count = self.get_item_count();
+
+        if count < 0 || idx >= count as u32 {
+            return LB_ERR;
+        }
+
+        unsafe {
+            let selected = self.is_selected(idx);
+            let data = user32::SendMessageW(self.window, LB_GETITEMDATA, idx as winapi::WPARAM, 0);
+
+            user32::SendMessageW(self.window, LB_DELETESTRING, idx as winapi::WPARAM, 0);
+
+            let new_idx = self.insert_item(idx, val);
+
+            if new_idx < 0 {
+                return new_idx;
+            }
+
+            user32::SendMessageW(self.window, LB_SETITEMDATA, new_idx as winapi::WPARAM, data);
+
+            if selected {
+                self.set_selected(new_idx as u32, true);
+            }
+
+            return new_idx;
+        }
+    }
+
+    /// Returns the index of the first item starting with prefix, ignoring
+    /// case, or -1 if there is none.
+    pub fn find_item(&self, prefix : &str) -> i32 {
+        let prefix = to_wchar(prefix);
+        let LB_FINDSTRING = 399;
+
+        unsafe {
+            return user32::SendMessageW(self.window, LB_FINDSTRING, -1isize as winapi::WPARAM, prefix.as_ptr() as winapi::LPARAM) as i32;
+        }
+    }
+
+    pub fn is_selected(&self, idx : u32) -> bool {
+        let LB_GETSEL = 391;
+
+        unsafe {
+            return user32::SendMessageW(self.window, LB_GETSEL, idx as winapi::WPARAM, 0) > 0;
+        }
+    }
+
+    /// Selects or deselects an item. In single selection mode selecting an
+    /// item deselects the others.
+    pub fn set_selected(&self, idx : u32, selected : bool) {
+        let LB_SETSEL = 389;
+        let LB_SETCURSEL = 390;
+        let LB_ERR = -1;
+
+        unsafe {
+            let result = user32::SendMessageW(self.window, LB_SETSEL, selected as winapi::WPARAM, idx as winapi::LPARAM);
+
+            //LB_SETSEL only works with multiple selection
+            if result == LB_ERR {
+                if selected {
+                    self.set_sel(idx);
+                } else if self.get_sel() == idx as i32 {
+                    user32::SendMessageW(self.window, LB_SETCURSEL, -1isize as winapi::WPARAM, 0);
+                }
+            }
+        }
+    }
+
+    /// Returns the indices of all selected items in ascending order.
+    pub fn get_selected_indices(&self) -> Vec<i32> {
+        let LB_GETSELCOUNT = 400;
+        let LB_GETSELITEMS = 401;
+
+        unsafe {
+            let count = user32::SendMessageW(self.window, LB_GETSELCOUNT, 0, 0);
+
+            //Single selection list box
+            if count < 0 {
+                let sel = self.get_sel();
+
+                return if sel < 0 {Vec::new()} else {vec![sel]};
+            }
+
+            let mut indices : Vec<i32> = vec![0; count as usize];
+
+            let count = user32::SendMessageW(self.window, LB_GETSELITEMS, count as winapi::WPARAM, indices.as_mut_ptr() as winapi::LPARAM);
+            indices.truncate(if count < 0 {0} else {count as usize});
+
+            return indices;
+        }
+    }
+
+    pub fn add_item(&self, val : &str) {
+        let val = to_wchar(val);
+        let LB_ADDSTRING = 384;
+
+        unsafe {
+            user32::SendMessageW(self.window, LB_ADDSTRING, 0, val.as_ptr() as winapi::LPARAM);
+        }
+    }
+
+    pub fn delete_item(&self, idx : u32) {
+        let LB_DELETESTRING = 386;
+
+        unsafe {
+            user32::SendMessageW(self.window, LB_DELETESTRING, idx, 0);
+        }
+    }
+    pub fn get_item_count(&self) -> i32 {
+        let LB_GETCOUNT = 395;
+
+        unsafe {
+    	       return user32::SendMessageW(self.window, LB_GETCOUNT, 0, 0);
+        }
+    }
+    pub fn clear(&self) {
+        let LB_RESETCONTENT = 388;
+
+        unsafe {
+            user32::SendMessageW(self.window, LB_RESETCONTENT, 0, 0);
+        }
+    }
+    pub fn get_sel(&self) -> i32 {
+        let LB_GETCURSEL = 392;
+
+        unsafe {
+               return user32::SendMessageW(self.window, LB_GETCURSEL, 0, 0);
+        }
+    }
+    pub fn set_sel(&self, idx : u32) {
+        let LB_SETCURSEL = 390;
+
+        unsafe {
+            user32::SendMessageW(self.window, LB_SETCURSEL, idx, 0);
+        }
+    }
+}
+
+/// A ListBox where every item carries a value of type T. The value stays
+/// with its item when other items are inserted, deleted or sorted. Methods
+/// that remove items take &mut self so that no borrowed value outlives its
+/// item. The ListBox methods that leave the items in place are forwarded.
+/// The values are dropped when the list box window is destroyed.
+pub struct TypedListBox<T> {
+    list: ListBox,
+    _marker: PhantomData<T>
+}
+
+impl <T> Window for TypedListBox<T> {
+    fn get_hwnd(&self) -> winapi::HWND {
+		return self.list.window;
+	}
+}
+
+
+impl <T> TypedListBox<T> {
+    pub fn new(parent: &Window, id: u16, x: i32, y: i32, width: i32, height: i32) -> TypedListBox<T> {
+        TypedListBox::with_options(parent, id, x, y, width, height, &ListBoxOptions::new())
+    }
+
+    pub fn with_options(parent: &Window, id: u16, x: i32, y: i32, width: i32, height: i32, options: &ListBoxOptions) -> TypedListBox<T> {
+        let list = ListBox::with_options(parent, id, x, y, width, height, options);
+        let drop_data: unsafe fn(winapi::LPARAM) = drop_list_data::<T>;
+
+        unsafe {
+            comctl32::SetWindowSubclass(list.window, Some(typed_list_proc), 0, drop_data as winapi::DWORD_PTR);
+        }
+
+        TypedListBox {
+            list: list,
+            _marker: PhantomData
+        }
+    }
+
+    /// Appends the item, or places it in sorted order. Returns its index.
+    pub fn add_item(&mut self, val : &str, data : T) -> i32 {
+        let count = self.list.get_item_count() as u32;
+
+        self.insert_item(count, val, data)
+    }
+
+    pub fn insert_item(&mut self, idx : u32, val : &str, data : T) -> i32 {
+        let LB_SETITEMDATA = 410;
+        let idx = self.list.insert_item(idx, val);
+
+        if idx >= 0 {
+            let data = Box::into_raw(Box::new(data));
+
+            unsafe {
+                user32::SendMessageW(self.list.window, LB_SETITEMDATA, idx as winapi::WPARAM, data as winapi::LPARAM);
+            }
+        }
+
+        idx
+    }
+
+    fn get_data_ptr(&self, idx : u32) -> *mut T {
+        let LB_GETITEMDATA = 409;
+        let LB_ERR = -1;
+
+        unsafe {
+            let data = user32::SendMessageW(self.list.window, LB_GETITEMDATA, idx as winapi::WPARAM, 0);
+
+            if data == LB_ERR {
+                return ptr::null_mut();
+            }
+
+            data as *mut T
+        }
+    }
+
+    pub fn get_item_text(&self, idx : u32) -> String {
+        self.list.get_item_text(idx)
+    }
+
+    /// Replaces the text of an item, keeping its data. See
+    /// ListBox::set_item_text().
+    pub fn set_item_text(&mut self, idx : u32, val : &str) -> i32 {
+        let data = self.get_data_ptr(idx);
+        let new_idx = self.list.set_item_text(idx, val);
+
+        //The item is gone, and its value with it
+        if new_idx < 0 && !data.is_null() {
+            unsafe {
+                drop(Box::from_raw(data));
+            }
+        }
+
+        new_idx
+    }
+
+    pub fn find_item(&self, prefix : &str) -> i32 {
+        self.list.find_item(prefix)
+    }
+
+    pub fn is_selected(&self, idx : u32) -> bool {
+        self.list.is_selected(idx)
+    }
+
+    pub fn set_selected(&self, idx : u32, selected : bool) {
+        self.list.set_selected(idx, selected)
+    }
+
+    pub fn get_selected_indices(&self) -> Vec<i32> {
+        self.list.get_selected_indices()
+    }
+
+    pub fn get_item_count(&self) -> i32 {
+        self.list.get_item_count()
+    }
+
+    pub fn get_sel(&self) -> i32 {
+        self.list.get_sel()
+    }
+
+    pub fn set_sel(&self, idx : u32) {
+        self.list.set_sel(idx)
+    }
+
+    pub fn get_data(&self, idx : u32) -> Option<&T> {
+        unsafe {
+            self.get_data_ptr(idx).as_ref()
+        }
+    }
+
+    pub fn get_data_mut(&mut self, idx : u32) -> Option<&mut T> {
+        unsafe {
+            self.get_data_ptr(idx).as_mut()
+        }
+    }
+
+    /// Returns the value of the selected item in a single selection list.
+    pub fn get_selected_data(&self) -> Option<&T> {
+        let sel = self.list.get_sel();
+
+        if sel < 0 {
+            return None;
+        }
+
+        self.get_data(sel as u32)
+    }
+
+    /// Removes the item and returns its value.
+    pub fn delete_item(&mut self, idx : u32) -> Option<T> {
+        let data = self.get_data_ptr(idx);
+
+        self.list.delete_item(idx);
+
+        if data.is_null() {
+            None
+        } else {
+            unsafe {
+                Some(*Box::from_raw(data))
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for idx in 0..self.list.get_item_count() {
+            let data = self.get_data_ptr(idx as u32);
+
+            if !data.is_null() {
+                unsafe {
+                    drop(Box::from_raw(data));
+                }
+            }
+        }
+
+        self.list.clear();
+    }
+}
+
+//Drops a value stored as item data by TypedListBox or TreeView
+unsafe fn drop_list_data<T>(data: winapi::LPARAM) {
+    drop(Box::from_raw(data as *mut T));
+}
+
+//Drops the values of a TypedListBox when the list box is destroyed. That
+//usually happens with its parent, while the TypedListBox may live on. The
+//list box empties itself on WM_DESTROY, so the values are taken first.
+unsafe extern "system" fn typed_list_proc(
+    window: winapi::HWND,
+    message: winapi::UINT,
+    w_param: winapi::WPARAM,
+    l_param: winapi::LPARAM,
+    _id: winapi::UINT_PTR,
+    drop_data: winapi::DWORD_PTR) -> winapi::LRESULT {
+
+    if message == winapi::WM_DESTROY {
+        let LB_GETITEMDATA = 409;
+        let LB_SETITEMDATA = 410;
+        let LB_GETCOUNT = 395;
+        let LB_ERR = -1;
+        let drop_data: unsafe fn(winapi::LPARAM) = mem::transmute(drop_data);
+
+        for idx in 0..user32::SendMessageW(window, LB_GETCOUNT, 0, 0) {
+            let data = user32::SendMessageW(window, LB_GETITEMDATA, idx as winapi::WPARAM, 0);
+
+            if data != LB_ERR && data != 0 {
+                user32::SendMessageW(window, LB_SETITEMDATA, idx as winapi::WPARAM, 0);
+                drop_data(data);
+            }
+        }
+
+        comctl32::RemoveWindowSubclass(window, Some(typed_list_proc), 0);
+    }
+
+    comctl32::DefSubclassProc(window, message, w_param, l_param)
+}
+
+/// Supplies the rows of a VirtualList on demand.
+pub trait ListModel {
+    fn len(&self) -> usize;
+    fn item(&self, index: usize) -> String;
+}
+
+/// A list that asks a ListModel for the rows it displays instead of
+/// storing them. It can show millions of rows. Call refresh() or use a
+/// ListNotifier when the model changes. The parent must be a howl window
+/// (Frame or Canvas) since it draws the rows.
+pub struct VirtualList {
+    window : winapi::HWND,
+    model : Box<Rc<ListModel>>
+}
+
+impl Window for VirtualList {
+    fn get_hwnd(&self) -> winapi::HWND {
+		return self.window;
+	}
+}
+
+impl VirtualList {
+    pub fn new(parent: &Window, id: u16, x: i32, y: i32, width: i32, height: i32, model: Rc<ListModel>) -> VirtualList {
+        let LBS_NOTIFY = 1;
+        let LBS_OWNERDRAWFIXED = 0x0010;
+        let LBS_NOINTEGRALHEIGHT = 0x0100;
+        let LBS_NODATA = 0x2000;
+
+        let wnd = WindowBuilder::new()
+            .class_name("LISTBOX")
+            .style(winapi::WS_VISIBLE | winapi::WS_VSCROLL | winapi::WS_CHILD |
+                LBS_NOTIFY | LBS_OWNERDRAWFIXED | LBS_NOINTEGRALHEIGHT | LBS_NODATA)
+            .position(x, y)
+            .size(width, height)
+            .parent(parent.get_hwnd())
+            .id(id)
+            .create();
+        let model = Box::new(model);
+
+        set_prop(wnd, "cwnd.model", &*model as *const Rc<ListModel> as winapi::HANDLE);
+        refresh_virtual_list(wnd);
+
+        VirtualList {
+            window: wnd,
+            model: model
+        }
+    }
+
+    pub fn get_model(&self) -> Rc<ListModel> {
+        (*self.model).clone()
+    }
+
+    /// Picks up a change in the number of rows and redraws.
+    pub fn refresh(&self) {
+        refresh_virtual_list(self.window);
+    }
+
+    /// Redraws the rows from first to last, inclusive, for when their
+    /// content changed but the number of rows did not. The bounds may be
+    /// given in either order; rows past the end are ignored.
+    pub fn refresh_rows(&self, first : usize, last : usize) {
+        let LB_GETITEMRECT = 408;
+        let count = self.get_item_count();
+
+        if count <= 0 {
+            return;
+        }
+
+        let last_row = count as usize - 1;
+        let (first, last) = if first <= last {(first, last)} else {(last, first)};
+
+        if first > last_row {
+            return;
+        }
+
+        for idx in first..=last.min(last_row) {
+            let mut rect = winapi::RECT {
+                top: 0, left: 0, right: 0, bottom: 0
+            };
+
+            unsafe {
+                if user32::SendMessageW(self.window, LB_GETITEMRECT, idx as winapi::WPARAM, &mut rect as *mut winapi::RECT as winapi::LPARAM) < 0 {
+                    break;
+                }
+            }
+
+            self.invalidate_rect(&rect);
+        }
+    }
+
+    /// Returns a handle the model can keep to announce its own changes.
+    pub fn notifier(&self) -> ListNotifier {
+        ListNotifier {
+            window: self.window
+        }
+    }
+
+    pub fn get_item_count(&self) -> i32 {
+        let LB_GETCOUNT = 395;
+
+        unsafe {
+    	       return user32::SendMessageW(self.window, LB_GETCOUNT, 0, 0);
+        }
+    }
+
+    pub fn get_sel(&self) -> i32 {
+        let LB_GETCURSEL = 392;
+
+        unsafe {
+               return user32::SendMessageW(self.window, LB_GETCURSEL, 0, 0);
+        }
+    }
+
+    pub fn set_sel(&self, idx : u32) {
+        let LB_SETCURSEL = 390;
+
+        unsafe {
+            user32::SendMessageW(self.window, LB_SETCURSEL, idx as winapi::WPARAM, 0);
+        }
+    }
+
+    /// Scrolls so that the row is visible.
+    pub fn ensure_visible(&self, idx : u32) {
+        let LB_SETTOPINDEX = 407;
+        let LB_GETTOPINDEX = 398;
+        let LB_GETITEMHEIGHT = 417;
+
+        unsafe {
+            let top = user32::SendMessageW(self.window, LB_GETTOPINDEX, 0, 0) as u32;
+            let item_height = user32::SendMessageW(self.window, LB_GETITEMHEIGHT, 0, 0) as i32;
+            let mut client = winapi::RECT {
+                top: 0, left: 0, right: 0, bottom: 0
+            };
+
+            user32::GetClientRect(self.window, &mut client);
+
+            let visible = if item_height > 0 {(client.bottom / item_height) as u32} else {1};
+
+            if idx < top {
+                user32::SendMessageW(self.window, LB_SETTOPINDEX, idx as winapi::WPARAM, 0);
+            } else if visible > 0 && idx >= top + visible {
+                user32::SendMessageW(self.window, LB_SETTOPINDEX, (idx + 1 - visible) as winapi::WPARAM, 0);
+            }
+        }
+    }
+}
+
+impl Drop for VirtualList {
+    fn drop(&mut self) {
+        //The model is about to go away. Stop the control from drawing it.
+        set_prop(self.window, "cwnd.model", ptr::null_mut());
+    }
+}
+
+/// Lets a ListModel tell its VirtualList about changes. Must be used on the
+/// UI thread. Calls after the list is dropped are ignored.
+#[derive(Clone)]
+pub struct ListNotifier {
+    window : winapi::HWND
+}
+
+impl ListNotifier {
+    /// Same as VirtualList::refresh().
+    pub fn changed(&self) {
+        refresh_virtual_list(self.window);
+    }
+}
+
+/// A multi-column list with headers (report mode list view). Rows can be
+/// sorted with sort_by(), usually from on_column_click().
+pub struct ListView {
+    window : winapi::HWND
+}
+
+impl Window for ListView {
+    fn get_hwnd(&self) -> winapi::HWND {
+		return self.window;
+	}
+}
+
+impl ListView {
+    pub fn new(parent: &Window, id: u16, x: i32, y: i32, width: i32, height: i32) -> ListView {
+        let LVS_REPORT = 0x0001;
+        let LVS_SHOWSELALWAYS = 0x0008;
+        let LVM_SETEXTENDEDLISTVIEWSTYLE = 0x1036;
+        let LVS_EX_GRIDLINES = 0x0001;
+        let LVS_EX_FULLROWSELECT = 0x0020;
+
+        let wnd = WindowBuilder::new()
+            .class_name("SysListView32")
+            .style(winapi::WS_VISIBLE | winapi::WS_TABSTOP | winapi::WS_CHILD | winapi::WS_BORDER | LVS_REPORT | LVS_SHOWSELALWAYS)
+            .position(x, y)
+            .size(width, height)
+            .parent(parent.get_hwnd())
+            .id(id)
+            .create();
+
+        unsafe {
+            user32::SendMessageW(wnd, LVM_SETEXTENDEDLISTVIEWSTYLE, 0, LVS_EX_FULLROWSELECT | LVS_EX_GRIDLINES);
+        }
+
+        ListView {
+            window: wnd
+        }
+    }
+
+    fn get_header(&self) -> winapi::HWND {
+        let LVM_GETHEADER = 0x101F;
+
+        unsafe {
+            user32::SendMessageW(self.window, LVM_GETHEADER, 0, 0) as winapi::HWND
+        }
+    }
+
+    pub fn get_column_count(&self) -> i32 {
+        let HDM_GETITEMCOUNT = 0x1200;
+
+        unsafe {
+            return user32::SendMessageW(self.get_header(), HDM_GETITEMCOUNT, 0, 0) as i32;
+        }
+    }
+
+    /// Appends a column and returns its index. Users can resize columns by
+    /// dragging the header dividers.
+    pub fn add_column(&self, title: &str, width: i32) -> i32 {
+        let LVM_INSERTCOLUMNW = 0x1061;
+        let LVCF_FMT = 0x0001;
+        let LVCF_WIDTH = 0x0002;
+        let LVCF_TEXT = 0x0004;
+        let LVCFMT_LEFT = 0x0000;
+        let title = to_wchar(title);
+
+        unsafe {
+            let mut column: winapi::LVCOLUMNW = mem::zeroed();
+
+            column.mask = LVCF_FMT | LVCF_WIDTH | LVCF_TEXT;
+            column.fmt = LVCFMT_LEFT;
+            column.cx = width;
+            column.pszText = title.as_ptr() as *mut u16;
+
+            return user32::SendMessageW(self.window, LVM_INSERTCOLUMNW, self.get_column_count() as winapi::WPARAM,
+                &column as *const winapi::LVCOLUMNW as winapi::LPARAM) as i32;
+        }
+    }
+
+    pub fn get_row_count(&self) -> i32 {
+        let LVM_GETITEMCOUNT = 0x1004;
+
+        unsafe {
+            return user32::SendMessageW(self.window, LVM_GETITEMCOUNT, 0, 0) as i32;
+        }
+    }
+
+    /// Inserts a row before row, or appends it if row is past the end.
+    /// cells holds the text of the columns from left to right. Returns the
+    /// index of the new row.
+    pub fn insert_row(&self, row: u32, cells: &[&str]) -> i32 {
+        let LVM_INSERTITEMW = 0x104D;
+        let LVIF_TEXT = 0x0001;
+        let first = to_wchar(cells.first().map(|c| *c).unwrap_or(""));
+
+        let row = unsafe {
+            let mut item: winapi::LVITEMW = mem::zeroed();
+
+            item.mask = LVIF_TEXT;
+            item.iItem = row as i32;
+            item.pszText = first.as_ptr() as *mut u16;
+
+            user32::SendMessageW(self.window, LVM_INSERTITEMW, 0, &item as *const winapi::LVITEMW as winapi::LPARAM) as i32
+        };
+
+        if row >= 0 {
+            for (col, cell) in cells.iter().enumerate().skip(1) {
+                self.set_cell_text(row as u32, col as u32, cell);
+            }
+        }
+
+        row
+    }
+
+    pub fn add_row(&self, cells: &[&str]) -> i32 {
+        let count = self.get_row_count() as u32;
+
+        self.insert_row(count, cells)
+    }
+
+    pub fn set_cell_text(&self, row: u32, col: u32, text: &str) {
+        let LVM_SETITEMTEXTW = 0x1074;
+        let text = to_wchar(text);
+
+        unsafe {
+            let mut item: winapi::LVITEMW = mem::zeroed();
+
+            item.iSubItem = col as i32;
+            item.pszText = text.as_ptr() as *mut u16;
+
+            user32::SendMessageW(self.window, LVM_SETITEMTEXTW, row as winapi::WPARAM, &item as *const winapi::LVITEMW as winapi::LPARAM);
+        }
+    }
+
+    pub fn get_cell_text(&self, row: u32, col: u32) -> String {
+        let LVM_GETITEMTEXTW = 0x1073;
+        let mut size = 256;
+
+        //The control does not report the text length. Grow the buffer
+        //until the text fits.
+        loop {
+            let mut v : Vec<u16> = vec![0; size];
+
+            let len = unsafe {
+                let mut item: winapi::LVITEMW = mem::zeroed();
+
+                item.iSubItem = col as i32;
+                item.pszText = v.as_mut_ptr();
+                item.cchTextMax = size as i32;
+
+                user32::SendMessageW(self.window, LVM_GETITEMTEXTW, row as winapi::WPARAM, &mut item as *mut winapi::LVITEMW as winapi::LPARAM) as usize
+            };
+
+            if len < size - 1 {
+                v.truncate(len);
+
+                return String::from_utf16_lossy(&v[..]);
+            }
+
+            size = size * 2;
+        }
+    }
+
+    pub fn delete_row(&self, row: u32) {
+        let LVM_DELETEITEM = 0x1008;
+
+        unsafe {
+            user32::SendMessageW(self.window, LVM_DELETEITEM, row as winapi::WPARAM, 0);
+        }
+    }
+
+    pub fn clear(&self) {
+        let LVM_DELETEALLITEMS = 0x1009;
+
+        unsafe {
+            user32::SendMessageW(self.window, LVM_DELETEALLITEMS, 0, 0);
+        }
+    }
+
+    pub fn get_selected_rows(&self) -> Vec<i32> {
+        let LVM_GETNEXTITEM = 0x100C;
+        let LVNI_SELECTED = 0x0002;
+        let mut rows = Vec::new();
+        let mut row = -1;
+
+        loop {
+            row = unsafe {
+                user32::SendMessageW(self.window, LVM_GETNEXTITEM, row as winapi::WPARAM, LVNI_SELECTED) as i32
+            };
+
+            if row < 0 {
+                return rows;
+            }
+
+            rows.push(row);
+        }
+    }
+
+    pub fn set_selected(&self, row: u32, selected: bool) {
+        let LVM_SETITEMSTATE = 0x102B;
+        let LVIS_SELECTED = 0x0002;
+
+        unsafe {
+            let mut item: winapi::LVITEMW = mem::zeroed();
+
+            item.stateMask = LVIS_SELECTED;
+            item.state = if selected {LVIS_SELECTED} else {0};
+
+            user32::SendMessageW(self.window, LVM_SETITEMSTATE, row as winapi::WPARAM, &item as *const winapi::LVITEMW as winapi::LPARAM);
+        }
+    }
+
+    /// Reorders the rows. The closure compares the cell texts of two rows.
+    /// The texts are read before sorting starts, since the list view can
+    /// not be queried while it sorts.
+    pub fn sort_by<F>(&self, mut compare: F) where F: FnMut(&[String], &[String]) -> Ordering {
+        let LVM_SETITEMW = 0x104C;
+        let LVM_SORTITEMS = 0x1030;
+        let LVIF_PARAM = 0x0004;
+
+        struct SortState<'a, F: 'a> {
+            rows: Vec<Vec<String>>,
+            compare: &'a mut F
+        }
+
+        unsafe extern "system" fn compare_rows<F>(row1: winapi::LPARAM, row2: winapi::LPARAM, data: winapi::LPARAM) -> i32
+            where F: FnMut(&[String], &[String]) -> Ordering {
+
+            let state = &mut *(data as *mut SortState<F>);
+
+            match (state.compare)(&state.rows[row1 as usize], &state.rows[row2 as usize]) {
+                Ordering::Less => -1,
+                Ordering::Equal => 0,
+                Ordering::Greater => 1
+            }
+        }
+
+        let columns = self.get_column_count().max(0) as u32;
+        let rows = self.get_row_count().max(0) as u32;
+        let mut state = SortState {
+            rows: (0..rows).map(|row| (0..columns).map(|col| self.get_cell_text(row, col)).collect()).collect(),
+            compare: &mut compare
+        };
+
+        unsafe {
+            //Tag each row with its index into the snapshot
+            for row in 0..rows {
+                let mut item: winapi::LVITEMW = mem::zeroed();
+
+                item.mask = LVIF_PARAM;
+                item.iItem = row as i32;
+                item.lParam = row as winapi::LPARAM;
+                user32::SendMessageW(self.window, LVM_SETITEMW, 0, &item as *const winapi::LVITEMW as winapi::LPARAM);
+            }
+
+            user32::SendMessageW(self.window, LVM_SORTITEMS, &mut state as *mut SortState<F> as winapi::WPARAM,
+                compare_rows::<F> as winapi::LPARAM);
+        }
+    }
+
+    /// Shows a sort arrow on the column header. None removes the arrow.
+    /// Arrows on other columns are removed.
+    pub fn set_sort_indicator(&self, col: u32, ascending: Option<bool>) {
+        let HDM_GETITEMW = 0x120B;
+        let HDM_SETITEMW = 0x120C;
+        let HDI_FORMAT = 0x0004;
+        let HDF_SORTDOWN = 0x0200;
+        let HDF_SORTUP = 0x0400;
+        let header = self.get_header();
+
+        for idx in 0..self.get_column_count() {
+            unsafe {
+                let mut item: winapi::HDITEMW = mem::zeroed();
+
+                item.mask = HDI_FORMAT;
+                user32::SendMessageW(header, HDM_GETITEMW, idx as winapi::WPARAM, &mut item as *mut winapi::HDITEMW as winapi::LPARAM);
+
+                item.fmt = item.fmt & !(HDF_SORTUP | HDF_SORTDOWN);
+
+                if idx as u32 == col {
+                    item.fmt = item.fmt | match ascending {
+                        Some(true) => HDF_SORTUP,
+                        Some(false) => HDF_SORTDOWN,
+                        None => 0
+                    };
+                }
+
+                user32::SendMessageW(header, HDM_SETITEMW, idx as winapi::WPARAM, &item as *const winapi::HDITEMW as winapi::LPARAM);
+            }
+        }
+    }
+
+    /// Sets the icons rows can show with set_row_icon(). All icons are
+    /// drawn at the size of the first one.
+    pub fn set_icons(&self, images: &[&Image]) {
+        let LVM_SETIMAGELIST = 0x1003;
+        let LVSIL_SMALL = 1;
+
+        unsafe {
+            //The control owns the list and destroys it with the window
+            let old = user32::SendMessageW(self.window, LVM_SETIMAGELIST, LVSIL_SMALL,
+                create_image_list(images) as winapi::LPARAM) as winapi::HIMAGELIST;
+
+            if !old.is_null() {
+                comctl32::ImageList_Destroy(old);
+            }
+        }
+    }
+
+    /// Shows the icon with the given index from set_icons() in the first
+    /// cell of the row.
+    pub fn set_row_icon(&self, row: u32, icon: i32) {
+        let LVM_SETITEMW = 0x104C;
+        let LVIF_IMAGE = 0x0002;
+
+        unsafe {
+            let mut item: winapi::LVITEMW = mem::zeroed();
+
+            item.mask = LVIF_IMAGE;
+            item.iItem = row as i32;
+            item.iImage = icon;
+
+            user32::SendMessageW(self.window, LVM_SETITEMW, 0, &item as *const winapi::LVITEMW as winapi::LPARAM);
+        }
+    }
+}
+
+/// A node of a TreeView.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TreeItem {
+    item : winapi::HTREEITEM
+}
+
+impl TreeItem {
+    pub fn get_handle(&self) -> winapi::HTREEITEM {
+        return self.item;
+    }
+}
+
+/// A tree where every node carries a value of type T. Nodes added with
+/// insert_lazy() show an expand button before they have children and
+/// on_item_populate() is called the first time they are expanded, so
+/// large hierarchies can be loaded on demand. The values are dropped
+/// when the tree view window is destroyed.
+pub struct TreeView<T> {
+    window : winapi::HWND,
+    _marker : PhantomData<T>
+}
+
+impl <T> Window for TreeView<T> {
+    fn get_hwnd(&self) -> winapi::HWND {
+		return self.window;
+	}
+}
+
+impl <T> TreeView<T> {
+    pub fn new(parent: &Window, id: u16, x: i32, y: i32, width: i32, height: i32, checkboxes: bool) -> TreeView<T> {
+        let TVS_HASBUTTONS = 0x0001;
+        let TVS_HASLINES = 0x0002;
+        let TVS_LINESATROOT = 0x0004;
+        let TVS_SHOWSELALWAYS = 0x0020;
+        let TVS_CHECKBOXES = 0x0100;
+
+        let wnd = WindowBuilder::new()
+            .class_name("SysTreeView32")
+            .style(winapi::WS_VISIBLE | winapi::WS_TABSTOP | winapi::WS_CHILD | winapi::WS_BORDER |
+                TVS_HASBUTTONS | TVS_HASLINES | TVS_LINESATROOT | TVS_SHOWSELALWAYS)
+            .position(x, y)
+            .size(width, height)
+            .parent(parent.get_hwnd())
+            .id(id)
+            .create();
+
+        //Check boxes must be turned on after the control is created
+        if checkboxes {
+            unsafe {
+                let style = user32::GetWindowLongW(wnd, winapi::GWL_STYLE) as winapi::DWORD;
+
+                user32::SetWindowLongW(wnd, winapi::GWL_STYLE, (style | TVS_CHECKBOXES) as winapi::LONG);
+            }
+        }
+
+        let drop_data: unsafe fn(winapi::LPARAM) = drop_list_data::<T>;
+
+        unsafe {
+            comctl32::SetWindowSubclass(wnd, Some(tree_view_proc), 0, drop_data as winapi::DWORD_PTR);
+        }
+
+        TreeView {
+            window: wnd,
+            _marker: PhantomData
+        }
+    }
+
+    fn insert_item(&mut self, parent: Option<TreeItem>, text: &str, data: T, lazy: bool) -> Option<TreeItem> {
+        let TVM_INSERTITEMW = 0x1132;
+        let TVIF_TEXT = 0x0001;
+        let TVIF_PARAM = 0x0004;
+        let TVIF_CHILDREN = 0x0040;
+        let TVI_ROOT = -0x10000isize as winapi::HTREEITEM;
+        let TVI_LAST = -0x0FFFEisize as winapi::HTREEITEM;
+        let text = to_wchar(text);
+
+        unsafe {
+            let mut insert: winapi::TVINSERTSTRUCTW = mem::zeroed();
+
+            insert.hParent = parent.map(|p| p.item).unwrap_or(TVI_ROOT);
+            insert.hInsertAfter = TVI_LAST;
+            insert.item.mask = TVIF_TEXT | TVIF_PARAM | if lazy {TVIF_CHILDREN} else {0};
+            insert.item.pszText = text.as_ptr() as *mut u16;
+            insert.item.lParam = Box::into_raw(Box::new(data)) as winapi::LPARAM;
+            insert.item.cChildren = 1;
+
+            let item = user32::SendMessageW(self.window, TVM_INSERTITEMW, 0,
+                &insert as *const winapi::TVINSERTSTRUCTW as winapi::LPARAM) as winapi::HTREEITEM;
+
+            if item.is_null() {
+                drop(Box::from_raw(insert.item.lParam as *mut T));
+
+                return None;
+            }
+
+            Some(TreeItem {
+                item: item
+            })
+        }
+    }
+
+    /// Adds a node as the last child of parent, or as a root node. Returns
+    /// None if the control could not add it.
+    pub fn insert(&mut self, parent: Option<TreeItem>, text: &str, data: T) -> Option<TreeItem> {
+        self.insert_item(parent, text, data, false)
+    }
+
+    /// Adds a node whose children are loaded in on_item_populate().
+    pub fn insert_lazy(&mut self, parent: Option<TreeItem>, text: &str, data: T) -> Option<TreeItem> {
+        self.insert_item(parent, text, data, true)
+    }
+
+    fn get_next(&self, item: Option<TreeItem>, relation: winapi::WPARAM) -> Option<TreeItem> {
+        let TVM_GETNEXTITEM = 0x110A;
+
+        unsafe {
+            let next = user32::SendMessageW(self.window, TVM_GETNEXTITEM, relation,
+                item.map(|i| i.item as winapi::LPARAM).unwrap_or(0)) as winapi::HTREEITEM;
+
+            if next.is_null() {
+                None
+            } else {
+                Some(TreeItem {
+                    item: next
+                })
+            }
+        }
+    }
+
+    pub fn get_parent(&self, item: TreeItem) -> Option<TreeItem> {
+        let TVGN_PARENT = 3;
+
+        self.get_next(Some(item), TVGN_PARENT)
+    }
+
+    /// Returns the children of item, or the root nodes for None.
+    pub fn get_children(&self, item: Option<TreeItem>) -> Vec<TreeItem> {
+        let TVGN_ROOT = 0;
+        let TVGN_NEXT = 1;
+        let TVGN_CHILD = 4;
+        let mut children = Vec::new();
+
+        let mut child = match item {
+            Some(item) => self.get_next(Some(item), TVGN_CHILD),
+            None => self.get_next(None, TVGN_ROOT)
+        };
+
+        while let Some(item) = child {
+            children.push(item);
+            child = self.get_next(Some(item), TVGN_NEXT);
+        }
+
+        children
+    }
+
+    fn get_data_ptr(&self, item: TreeItem) -> *mut T {
+        let TVM_GETITEMW = 0x113E;
+        let TVIF_PARAM = 0x0004;
+
+        unsafe {
+            let mut info: winapi::TVITEMW = mem::zeroed();
+
+            info.mask = TVIF_PARAM;
+            info.hItem = item.item;
+
+            if user32::SendMessageW(self.window, TVM_GETITEMW, 0, &mut info as *mut winapi::TVITEMW as winapi::LPARAM) == 0 {
+                return ptr::null_mut();
+            }
+
+            info.lParam as *mut T
+        }
+    }
+
+    pub fn get_data(&self, item: TreeItem) -> Option<&T> {
+        unsafe {
+            self.get_data_ptr(item).as_ref()
+        }
+    }
+
+    pub fn get_data_mut(&mut self, item: TreeItem) -> Option<&mut T> {
+        unsafe {
+            self.get_data_ptr(item).as_mut()
+        }
+    }
+
+    pub fn get_text(&self, item: TreeItem) -> String {
+        let TVM_GETITEMW = 0x113E;
+        let TVIF_TEXT = 0x0001;
+        let mut size = 256;
+
+        loop {
+            let mut v : Vec<u16> = vec![0; size];
+
+            unsafe {
+                let mut info: winapi::TVITEMW = mem::zeroed();
+
+                info.mask = TVIF_TEXT;
+                info.hItem = item.item;
+                info.pszText = v.as_mut_ptr();
+                info.cchTextMax = size as i32;
+
+                user32::SendMessageW(self.window, TVM_GETITEMW, 0, &mut info as *mut winapi::TVITEMW as winapi::LPARAM);
+            }
+
+            let len = v.iter().position(|c| *c == 0).unwrap_or(size);
+
+            if len < size - 1 {
+                v.truncate(len);
+
+                return String::from_utf16_lossy(&v[..]);
+            }
+
+            size = size * 2;
+        }
+    }
+
+    pub fn set_text(&self, item: TreeItem, text: &str) {
+        let TVM_SETITEMW = 0x113F;
+        let TVIF_TEXT = 0x0001;
+        let text = to_wchar(text);
+
+        unsafe {
+            let mut info: winapi::TVITEMW = mem::zeroed();
+
+            info.mask = TVIF_TEXT;
+            info.hItem = item.item;
+            info.pszText = text.as_ptr() as *mut u16;
+
+            user32::SendMessageW(self.window, TVM_SETITEMW, 0, &info as *const winapi::TVITEMW as winapi::LPARAM);
+        }
+    }
+
+    fn free_subtree(&mut self, item: TreeItem) {
+        for child in self.get_children(Some(item)) {
+            self.free_subtree(child);
+        }
+
+        let data = self.get_data_ptr(item);
+
+        if !data.is_null() {
+            unsafe {
+                drop(Box::from_raw(data));
+            }
+        }
+    }
+
+    /// Removes the node and all its descendants.
+    pub fn remove(&mut self, item: TreeItem) {
+        let TVM_DELETEITEM = 0x1101;
+
+        self.free_subtree(item);
+
+        unsafe {
+            user32::SendMessageW(self.window, TVM_DELETEITEM, 0, item.item as winapi::LPARAM);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for root in self.get_children(None) {
+            self.remove(root);
+        }
+    }
+
+    pub fn get_count(&self) -> i32 {
+        let TVM_GETCOUNT = 0x1105;
+
+        unsafe {
+            return user32::SendMessageW(self.window, TVM_GETCOUNT, 0, 0) as i32;
+        }
+    }
+
+    pub fn expand(&self, item: TreeItem) {
+        let TVM_EXPAND = 0x1102;
+        let TVE_EXPAND = 0x0002;
+
+        unsafe {
+            user32::SendMessageW(self.window, TVM_EXPAND, TVE_EXPAND, item.item as winapi::LPARAM);
+        }
+    }
+
+    pub fn collapse(&self, item: TreeItem) {
+        let TVM_EXPAND = 0x1102;
+        let TVE_COLLAPSE = 0x0001;
+
+        unsafe {
+            user32::SendMessageW(self.window, TVM_EXPAND, TVE_COLLAPSE, item.item as winapi::LPARAM);
+        }
+    }
+
+    fn get_state(&self, item: TreeItem, mask: winapi::UINT) -> winapi::UINT {
+        let TVM_GETITEMSTATE = 0x1127;
+
+        unsafe {
+            user32::SendMessageW(self.window, TVM_GETITEMSTATE, item.item as winapi::WPARAM, mask as winapi::LPARAM) as winapi::UINT
+        }
+    }
+
+    fn set_state(&self, item: TreeItem, state: winapi::UINT, mask: winapi::UINT) {
+        let TVM_SETITEMW = 0x113F;
+        let TVIF_STATE = 0x0008;
+
+        unsafe {
+            let mut info: winapi::TVITEMW = mem::zeroed();
+
+            info.mask = TVIF_STATE;
+            info.hItem = item.item;
+            info.state = state;
+            info.stateMask = mask;
+
+            user32::SendMessageW(self.window, TVM_SETITEMW, 0, &info as *const winapi::TVITEMW as winapi::LPARAM);
+        }
+    }
+
+    pub fn is_expanded(&self, item: TreeItem) -> bool {
+        let TVIS_EXPANDED = 0x0020;
+
+        self.get_state(item, TVIS_EXPANDED) & TVIS_EXPANDED != 0
+    }
+
+    pub fn get_selection(&self) -> Option<TreeItem> {
+        let TVGN_CARET = 9;
+
+        self.get_next(None, TVGN_CARET)
+    }
+
+    pub fn select(&self, item: TreeItem) {
+        let TVM_SELECTITEM = 0x110B;
+        let TVGN_CARET = 9;
+
+        unsafe {
+            user32::SendMessageW(self.window, TVM_SELECTITEM, TVGN_CARET, item.item as winapi::LPARAM);
+        }
+    }
+
+    /// Only meaningful if the tree was created with check boxes.
+    pub fn is_checked(&self, item: TreeItem) -> bool {
+        let TVIS_STATEIMAGEMASK = 0xF000;
+
+        //State image 1 is unchecked, 2 is checked
+        (self.get_state(item, TVIS_STATEIMAGEMASK) >> 12) == 2
+    }
+
+    pub fn set_checked(&self, item: TreeItem, checked: bool) {
+        let TVIS_STATEIMAGEMASK = 0xF000;
+
+        self.set_state(item, (if checked {2} else {1}) << 12, TVIS_STATEIMAGEMASK);
+    }
+}
+
+//Drops the value of item and all nodes after and below it
+unsafe fn drop_tree_data(window: winapi::HWND, item: winapi::HTREEITEM, drop_data: unsafe fn(winapi::LPARAM)) {
+    let TVM_GETNEXTITEM = 0x110A;
+    let TVM_GETITEMW = 0x113E;
+    let TVM_SETITEMW = 0x113F;
+    let TVIF_PARAM = 0x0004;
+    let TVGN_NEXT = 1;
+    let TVGN_CHILD = 4;
+    let mut item = item;
+
+    while !item.is_null() {
+        let child = user32::SendMessageW(window, TVM_GETNEXTITEM, TVGN_CHILD, item as winapi::LPARAM) as winapi::HTREEITEM;
+
+        drop_tree_data(window, child, drop_data);
+
+        let mut info: winapi::TVITEMW = mem::zeroed();
+
+        info.mask = TVIF_PARAM;
+        info.hItem = item;
+
+        if user32::SendMessageW(window, TVM_GETITEMW, 0, &mut info as *mut winapi::TVITEMW as winapi::LPARAM) != 0 &&
+            info.lParam != 0 {
+
+            let data = info.lParam;
+
+            info.lParam = 0;
+            user32::SendMessageW(window, TVM_SETITEMW, 0, &info as *const winapi::TVITEMW as winapi::LPARAM);
+            drop_data(data);
+        }
+
+        item = user32::SendMessageW(window, TVM_GETNEXTITEM, TVGN_NEXT, item as winapi::LPARAM) as winapi::HTREEITEM;
+    }
+}
+
+//Drops the values of a TreeView when the control is destroyed, which
+//deletes its nodes on WM_DESTROY.
+unsafe extern "system" fn tree_view_proc(
+    window: winapi::HWND,
+    message: winapi::UINT,
+    w_param: winapi::WPARAM,
+    l_param: winapi::LPARAM,
+    _id: winapi::UINT_PTR,
+    drop_data: winapi::DWORD_PTR) -> winapi::LRESULT {
+
+    if message == winapi::WM_DESTROY {
+        let TVM_GETNEXTITEM = 0x110A;
+        let TVGN_ROOT = 0;
+        let root = user32::SendMessageW(window, TVM_GETNEXTITEM, TVGN_ROOT, 0) as winapi::HTREEITEM;
+
+        drop_tree_data(window, root, mem::transmute(drop_data));
+        comctl32::RemoveWindowSubclass(window, Some(tree_view_proc), 0);
+    }
+
+    comctl32::DefSubclassProc(window, message, w_param, l_param)
+}
+
+/// The container shown for one tab of a TabControl. Add controls to it
+/// and attach an event handler to it like any Canvas.
+pub struct TabPage {
+    window : winapi::HWND
+}
+
+impl Window for TabPage {
+    fn get_hwnd(&self) -> winapi::HWND {
+		return self.window;
+	}
+}
+
+/// Tabs that each own a page. Only the page of the selected tab is
+/// visible. The parent gets on_tab_change() when the user switches tabs.
+pub struct TabControl {
+    window : winapi::HWND
+}
+
+impl Window for TabControl {
+    fn get_hwnd(&self) -> winapi::HWND {
+		return self.window;
+	}
+
+    fn resize(&self, x : i32, y : i32, width : i32, height : i32) {
+        unsafe {
+            user32::MoveWindow(self.window, x, y, width, height, 1);
+        }
+
+        layout_tab_pages(self.window);
+    }
+}
+
+impl TabControl {
+    pub fn new(parent: &Window, id: u16, x: i32, y: i32, width: i32, height: i32) -> TabControl {
+        let wnd = WindowBuilder::new()
+            .class_name("SysTabControl32")
+            .style(winapi::WS_VISIBLE | winapi::WS_TABSTOP | winapi::WS_CHILD | winapi::WS_CLIPCHILDREN)
+            .position(x, y)
+            .size(width, height)
+            .parent(parent.get_hwnd())
+            .id(id)
+            .create();
+        TabControl {
+            window: wnd
+        }
+    }
+
+    /// Appends a tab and returns its page. The first tab is selected
+    /// automatically.
+    pub fn add_tab(&self, title: &str) -> TabPage {
+        let TCM_INSERTITEMW = 0x133E;
+        let TCM_SETCURSEL = 0x130C;
+        let TCIF_TEXT = 0x0001;
+        let TCIF_PARAM = 0x0008;
+        let count = self.get_tab_count();
+
+        let page = WindowBuilder::new()
+            .class_name("HOWL")
+            .parent(self.window)
+            .style(winapi::WS_CHILD | winapi::WS_CLIPCHILDREN)
+            .position(0, 0)
+            .size(0, 0)
+            .create();
+        let title = to_wchar(title);
+
+        unsafe {
+            let mut item: winapi::TCITEMW = mem::zeroed();
+
+            item.mask = TCIF_TEXT | TCIF_PARAM;
+            item.pszText = title.as_ptr() as *mut u16;
+            item.lParam = page as winapi::LPARAM;
+
+            user32::SendMessageW(self.window, TCM_INSERTITEMW, count as winapi::WPARAM, &item as *const winapi::TCITEMW as winapi::LPARAM);
+
+            if count == 0 {
+                user32::SendMessageW(self.window, TCM_SETCURSEL, 0, 0);
+            }
+        }
+
+        layout_tab_pages(self.window);
+
+        TabPage {
+            window: page
+        }
+    }
+
+    /// Removes the tab and destroys its page along with the controls on it.
+    pub fn remove_tab(&self, index: u32) {
+        let TCM_DELETEITEM = 0x1308;
+        let TCM_SETCURSEL = 0x130C;
+        let page = get_tab_page(self.window, index as i32);
+        let selected = self.get_selected();
+
+        unsafe {
+            user32::SendMessageW(self.window, TCM_DELETEITEM, index as winapi::WPARAM, 0);
+
+            if !page.is_null() {
+                user32::DestroyWindow(page);
+            }
+
+            //Keep a tab selected if the selected one went away
+            if selected == index as i32 {
+                let count = self.get_tab_count();
+
+                if count > 0 {
+                    let next = if (index as i32) < count {index as i32} else {count - 1};
+
+                    user32::SendMessageW(self.window, TCM_SETCURSEL, next as winapi::WPARAM, 0);
+                }
+            }
+        }
+
+        layout_tab_pages(self.window);
+    }
+
+    pub fn rename_tab(&self, index: u32, title: &str) {
+        let TCM_SETITEMW = 0x133D;
+        let TCIF_TEXT = 0x0001;
+        let title = to_wchar(title);
+
+        unsafe {
+            let mut item: winapi::TCITEMW = mem::zeroed();
+
+            item.mask = TCIF_TEXT;
+            item.pszText = title.as_ptr() as *mut u16;
+
+            user32::SendMessageW(self.window, TCM_SETITEMW, index as winapi::WPARAM, &item as *const winapi::TCITEMW as winapi::LPARAM);
+        }
+    }
 
-            rect.left = point.x;
-            rect.top = point.y;
+    pub fn get_tab_title(&self, index: u32) -> String {
+        let TCM_GETITEMW = 0x133C;
+        let TCIF_TEXT = 0x0001;
+        let size = 256;
+        let mut v : Vec<u16> = vec![0; size];
 
-            let mut point = winapi::POINT {
-                x: rect.right,
-                y: rect.bottom
-            };
+        unsafe {
+            let mut item: winapi::TCITEMW = mem::zeroed();
 
-            user32::MapWindowPoints(0 as winapi::HWND,
-                user32::GetParent(self.get_hwnd()),
-                &mut point as *mut winapi::POINT, 1);
+            item.mask = TCIF_TEXT;
+            item.pszText = v.as_mut_ptr();
+            item.cchTextMax = size as i32;
+
+            user32::SendMessageW(self.window, TCM_GETITEMW, index as winapi::WPARAM, &mut item as *mut winapi::TCITEMW as winapi::LPARAM);
+        }
+
+        let len = v.iter().position(|c| *c == 0).unwrap_or(size);
+        v.truncate(len);
+
+        return String::from_utf16_lossy(&v[..]);
+    }
+
+    pub fn get_tab_count(&self) -> i32 {
+        let TCM_GETITEMCOUNT = 0x1304;
+
+        unsafe {
+            return user32::SendMessageW(self.window, TCM_GETITEMCOUNT, 0, 0) as i32;
+        }
+    }
+
+    pub fn get_page(&self, index: u32) -> Option<TabPage> {
+        let page = get_tab_page(self.window, index as i32);
+
+        if page.is_null() {
+            None
+        } else {
+            Some(TabPage {
+                window: page
+            })
+        }
+    }
+
+    /// Returns the index of the selected tab or -1 if there are no tabs.
+    pub fn get_selected(&self) -> i32 {
+        let TCM_GETCURSEL = 0x130B;
+
+        unsafe {
+            return user32::SendMessageW(self.window, TCM_GETCURSEL, 0, 0) as i32;
+        }
+    }
+
+    /// Selects a tab and shows its page. Does not call on_tab_change().
+    pub fn select(&self, index: u32) {
+        let TCM_SETCURSEL = 0x130C;
+
+        unsafe {
+            user32::SendMessageW(self.window, TCM_SETCURSEL, index as winapi::WPARAM, 0);
+        }
+
+        layout_tab_pages(self.window);
+    }
+}
+
+pub struct ProgressBar {
+    window : winapi::HWND
+}
+
+impl Window for ProgressBar {
+    fn get_hwnd(&self) -> winapi::HWND {
+		return self.window;
+	}
+}
+
+impl ProgressBar {
+    /// Creates a bar with a range of 0 to 100.
+    pub fn new(parent: &Window, x: i32, y: i32, width: i32, height: i32) -> ProgressBar {
+        let wnd = WindowBuilder::new()
+            .class_name("msctls_progress32")
+            .style(winapi::WS_VISIBLE | winapi::WS_CHILD)
+            .position(x, y)
+            .size(width, height)
+            .parent(parent.get_hwnd())
+            .create();
+        ProgressBar {
+            window: wnd
+        }
+    }
+
+    pub fn set_range(&self, min: i32, max: i32) {
+        let PBM_SETRANGE32 = 0x0406;
+
+        unsafe {
+            user32::SendMessageW(self.window, PBM_SETRANGE32, min as winapi::WPARAM, max as winapi::LPARAM);
+        }
+    }
+
+    pub fn set_value(&self, value: i32) {
+        let PBM_SETPOS = 0x0402;
+
+        unsafe {
+            user32::SendMessageW(self.window, PBM_SETPOS, value as winapi::WPARAM, 0);
+        }
+    }
+
+    pub fn get_value(&self) -> i32 {
+        let PBM_GETPOS = 0x0408;
+
+        unsafe {
+            return user32::SendMessageW(self.window, PBM_GETPOS, 0, 0) as i32;
+        }
+    }
+
+    /// Marquee mode shows continuous activity for operations of unknown
+    /// length. interval is the animation delay in milliseconds.
+    pub fn set_marquee(&self, marquee: bool, interval: u32) {
+        let PBS_MARQUEE = 0x0008;
+        let PBM_SETMARQUEE = 0x040A;
+
+        unsafe {
+            let style = user32::GetWindowLongW(self.window, winapi::GWL_STYLE) as winapi::DWORD;
+            let style = if marquee {style | PBS_MARQUEE} else {style & !PBS_MARQUEE};
+
+            if marquee {
+                user32::SetWindowLongW(self.window, winapi::GWL_STYLE, style as winapi::LONG);
+                user32::SendMessageW(self.window, PBM_SETMARQUEE, 1, interval as winapi::LPARAM);
+            } else {
+                user32::SendMessageW(self.window, PBM_SETMARQUEE, 0, 0);
+                user32::SetWindowLongW(self.window, winapi::GWL_STYLE, style as winapi::LONG);
+            }
+        }
+    }
+
+    pub fn is_marquee(&self) -> bool {
+        let PBS_MARQUEE = 0x0008;
+
+        unsafe {
+            return user32::GetWindowLongW(self.window, winapi::GWL_STYLE) as winapi::DWORD & PBS_MARQUEE != 0;
+        }
+    }
+}
+
+/// A trackbar. The parent gets on_value_change() as the thumb moves.
+pub struct Slider {
+    window : winapi::HWND
+}
+
+impl Window for Slider {
+    fn get_hwnd(&self) -> winapi::HWND {
+		return self.window;
+	}
+}
+
+impl Slider {
+    /// Creates a slider with a range of 0 to 100.
+    pub fn new(parent: &Window, id: u16, x: i32, y: i32, width: i32, height: i32, vertical: bool) -> Slider {
+        let TBS_AUTOTICKS = 0x0001;
+        let TBS_VERT = 0x0002;
+
+        let wnd = WindowBuilder::new()
+            .class_name("msctls_trackbar32")
+            .style(winapi::WS_VISIBLE | winapi::WS_TABSTOP | winapi::WS_CHILD | TBS_AUTOTICKS |
+                if vertical {TBS_VERT} else {0})
+            .position(x, y)
+            .size(width, height)
+            .parent(parent.get_hwnd())
+            .id(id)
+            .create();
+        Slider {
+            window: wnd
+        }
+    }
+
+    pub fn set_range(&self, min: i32, max: i32) {
+        let TBM_SETRANGEMIN = 0x0407;
+        let TBM_SETRANGEMAX = 0x0408;
+
+        unsafe {
+            user32::SendMessageW(self.window, TBM_SETRANGEMIN, 0, min as winapi::LPARAM);
+            user32::SendMessageW(self.window, TBM_SETRANGEMAX, 1, max as winapi::LPARAM);
+        }
+    }
+
+    pub fn get_range(&self) -> (i32, i32) {
+        let TBM_GETRANGEMIN = 0x0401;
+        let TBM_GETRANGEMAX = 0x0402;
+
+        unsafe {
+            (user32::SendMessageW(self.window, TBM_GETRANGEMIN, 0, 0) as i32,
+                user32::SendMessageW(self.window, TBM_GETRANGEMAX, 0, 0) as i32)
+        }
+    }
+
+    pub fn set_value(&self, value: i32) {
+        let TBM_SETPOS = 0x0405;
+
+        unsafe {
+            user32::SendMessageW(self.window, TBM_SETPOS, 1, value as winapi::LPARAM);
+        }
+    }
+
+    pub fn get_value(&self) -> i32 {
+        let TBM_GETPOS = 0x0400;
+
+        unsafe {
+            return user32::SendMessageW(self.window, TBM_GETPOS, 0, 0) as i32;
+        }
+    }
+
+    /// Draws a tick mark every frequency values.
+    pub fn set_tick_frequency(&self, frequency: i32) {
+        let TBM_SETTICFREQ = 0x0414;
+
+        unsafe {
+            user32::SendMessageW(self.window, TBM_SETTICFREQ, frequency as winapi::WPARAM, 0);
+        }
+    }
+
+    /// How far Page Up and Page Down or clicking the channel moves the thumb.
+    pub fn set_page_size(&self, size: i32) {
+        let TBM_SETPAGESIZE = 0x0415;
+
+        unsafe {
+            user32::SendMessageW(self.window, TBM_SETPAGESIZE, 0, size as winapi::LPARAM);
+        }
+    }
+}
+
+/// A numeric up-down control attached to an edit box. The parent gets
+/// on_value_change() when the arrows are used, and on_command() with
+/// EN_CHANGE when the number is typed.
+pub struct SpinBox {
+    edit : winapi::HWND,
+    updown : winapi::HWND
+}
+
+impl Window for SpinBox {
+    fn get_hwnd(&self) -> winapi::HWND {
+		return self.edit;
+	}
+
+    fn resize(&self, x : i32, y : i32, width : i32, height : i32) {
+        let UDM_SETBUDDY = 0x0469;
+
+        unsafe {
+            user32::MoveWindow(self.edit, x, y, width, height, 1);
+            //Places the arrows inside the right edge of the edit again
+            user32::SendMessageW(self.updown, UDM_SETBUDDY, self.edit as winapi::WPARAM, 0);
+        }
+    }
+
+    fn show(&self) {
+		unsafe {
+			user32::ShowWindow(self.edit, 5);
+			user32::ShowWindow(self.updown, 5);
+		}
+	}
+
+	fn hide(&self) {
+		unsafe {
+			user32::ShowWindow(self.edit, 0);
+			user32::ShowWindow(self.updown, 0);
+		}
+	}
+}
+
+impl SpinBox {
+    /// Creates a spin box with a range of 0 to 100 and a step of 1.
+    pub fn new(parent: &Window, id: u16, x: i32, y: i32, width: i32, height: i32) -> SpinBox {
+        let UDS_SETBUDDYINT = 0x0002;
+        let UDS_ALIGNRIGHT = 0x0004;
+        let UDS_ARROWKEYS = 0x0020;
+        let UDS_NOTHOUSANDS = 0x0080;
+        let UDM_SETBUDDY = 0x0469;
+
+        let edit = WindowBuilder::new()
+            .class_name("EDIT")
+            .style(winapi::WS_VISIBLE | winapi::WS_TABSTOP | winapi::WS_CHILD)
+            .extra_style(winapi::WS_EX_CLIENTEDGE)
+            .position(x, y)
+            .size(width, height)
+            .parent(parent.get_hwnd())
+            .id(id)
+            .create();
+        //The arrows have no id of their own. Their events report the id
+        //of the edit.
+        let updown = WindowBuilder::new()
+            .class_name("msctls_updown32")
+            .style(winapi::WS_VISIBLE | winapi::WS_CHILD | UDS_SETBUDDYINT | UDS_ALIGNRIGHT | UDS_ARROWKEYS | UDS_NOTHOUSANDS)
+            .position(0, 0)
+            .size(0, 0)
+            .parent(parent.get_hwnd())
+            .create();
+
+        unsafe {
+            user32::SendMessageW(updown, UDM_SETBUDDY, edit as winapi::WPARAM, 0);
+        }
+
+        let spin = SpinBox {
+            edit: edit,
+            updown: updown
+        };
+
+        spin.set_range(0, 100);
+        spin.set_value(0);
+
+        spin
+    }
+
+    pub fn set_range(&self, min: i32, max: i32) {
+        let UDM_SETRANGE32 = 0x046F;
+
+        unsafe {
+            user32::SendMessageW(self.updown, UDM_SETRANGE32, min as winapi::WPARAM, max as winapi::LPARAM);
+        }
+    }
+
+    /// Sets how much one click on an arrow changes the value.
+    pub fn set_step(&self, step: u32) {
+        let UDM_SETACCEL = 0x046B;
+        let accel = winapi::UDACCEL {
+            nSec: 0,
+            nInc: step
+        };
+
+        unsafe {
+            user32::SendMessageW(self.updown, UDM_SETACCEL, 1, &accel as *const winapi::UDACCEL as winapi::LPARAM);
+        }
+    }
+
+    pub fn set_value(&self, value: i32) {
+        let UDM_SETPOS32 = 0x0471;
+
+        unsafe {
+            user32::SendMessageW(self.updown, UDM_SETPOS32, 0, value as winapi::LPARAM);
+        }
+    }
+
+    /// Returns the value in the edit box, clamped to the range.
+    pub fn get_value(&self) -> i32 {
+        let UDM_GETPOS32 = 0x0472;
+
+        unsafe {
+            return user32::SendMessageW(self.updown, UDM_GETPOS32, 0, 0) as i32;
+        }
+    }
+
+    /// Destroys the edit box and its arrows.
+    pub fn destroy(&self) {
+        unsafe {
+            user32::DestroyWindow(self.updown);
+            user32::DestroyWindow(self.edit);
+        }
+    }
+}
+
+/// A bar along the bottom of a Frame with one or more text panes. The
+/// Frame reserves room for it; see Window::get_client_area().
+pub struct StatusBar {
+    window : winapi::HWND
+}
+
+impl Window for StatusBar {
+    fn get_hwnd(&self) -> winapi::HWND {
+		return self.window;
+	}
+}
+
+impl StatusBar {
+    pub fn new(parent: &Window) -> StatusBar {
+        let SBARS_SIZEGRIP = 0x0100;
+
+        let wnd = WindowBuilder::new()
+            .class_name("msctls_statusbar32")
+            .style(winapi::WS_VISIBLE | winapi::WS_CHILD | SBARS_SIZEGRIP)
+            .position(0, 0)
+            .size(0, 0)
+            .parent(parent.get_hwnd())
+            .create();
+
+        set_prop(parent.get_hwnd(), "cwnd.statusbar", wnd as winapi::HANDLE);
+        layout_bars(parent.get_hwnd());
+
+        StatusBar {
+            window: wnd
+        }
+    }
+
+    /// Splits the bar into panes of the given widths, from left to right.
+    /// A width of -1 makes a pane take up the rest of the bar.
+    pub fn set_parts(&self, widths: &[i32]) {
+        let SB_SETPARTS = 0x0404;
+        let mut edges = Vec::with_capacity(widths.len());
+        let mut right = 0;
+
+        for width in widths {
+            if *width < 0 {
+                edges.push(-1);
+                break;
+            }
+
+            right = right + width;
+            edges.push(right);
+        }
+
+        unsafe {
+            user32::SendMessageW(self.window, SB_SETPARTS, edges.len() as winapi::WPARAM, edges.as_ptr() as winapi::LPARAM);
+        }
+    }
+
+    pub fn set_part_text(&self, part: u32, text: &str) {
+        let SB_SETTEXTW = 0x040B;
+        let text = to_wchar(text);
+
+        unsafe {
+            user32::SendMessageW(self.window, SB_SETTEXTW, part as winapi::WPARAM, text.as_ptr() as winapi::LPARAM);
+        }
+    }
+
+    pub fn get_part_text(&self, part: u32) -> String {
+        let SB_GETTEXTW = 0x040D;
+        let SB_GETTEXTLENGTHW = 0x040C;
+
+        unsafe {
+            let len = winapi::LOWORD(user32::SendMessageW(self.window, SB_GETTEXTLENGTHW, part as winapi::WPARAM, 0) as winapi::DWORD) as usize;
+            let mut v : Vec<u16> = vec![0; len + 1];
+
+            user32::SendMessageW(self.window, SB_GETTEXTW, part as winapi::WPARAM, v.as_mut_ptr() as winapi::LPARAM);
+            v.truncate(len);
+
+            return String::from_utf16_lossy(&v[..]);
+        }
+    }
+}
+
+/// A bar of icon buttons along the top of a Frame. Clicks arrive as
+/// on_command() with the button id. Tooltip text is shown when hovering.
+/// The Frame reserves room for it; see Window::get_client_area().
+pub struct ToolBar {
+    window : winapi::HWND
+}
+
+impl Window for ToolBar {
+    fn get_hwnd(&self) -> winapi::HWND {
+		return self.window;
+	}
+}
+
+impl ToolBar {
+    pub fn new(parent: &Window) -> ToolBar {
+        let TBSTYLE_TOOLTIPS = 0x0100;
+        let TBSTYLE_FLAT = 0x0800;
+        let TBSTYLE_LIST = 0x1000;
+        let TB_BUTTONSTRUCTSIZE = 0x041E;
+        let TB_SETEXTENDEDSTYLE = 0x0454;
+        let TBSTYLE_EX_MIXEDBUTTONS = 0x0008;
+
+        let wnd = WindowBuilder::new()
+            .class_name("ToolbarWindow32")
+            .style(winapi::WS_VISIBLE | winapi::WS_CHILD | TBSTYLE_TOOLTIPS | TBSTYLE_FLAT | TBSTYLE_LIST)
+            .position(0, 0)
+            .size(0, 0)
+            .parent(parent.get_hwnd())
+            .create();
+
+        unsafe {
+            user32::SendMessageW(wnd, TB_BUTTONSTRUCTSIZE, mem::size_of::<winapi::TBBUTTON>() as winapi::WPARAM, 0);
+            //Button text is used as tooltip instead of being drawn
+            user32::SendMessageW(wnd, TB_SETEXTENDEDSTYLE, 0, TBSTYLE_EX_MIXEDBUTTONS);
+        }
 
-            rect.right = point.x;
-            rect.bottom = point.y;
+        set_prop(parent.get_hwnd(), "cwnd.toolbar", wnd as winapi::HANDLE);
+        layout_bars(parent.get_hwnd());
 
-            return rect;
+        ToolBar {
+            window: wnd
         }
     }
 
-    fn get_text_length(&self) -> usize {
-		unsafe {
-			return user32::SendMessageW(self.get_hwnd(), winapi::WM_GETTEXTLENGTH, 0, 0)
-                as usize;
-		}
-	}
-
-	fn set_text(&self, txt : &str) {
-		unsafe {
-			user32::SendMessageW(self.get_hwnd(), winapi::WM_SETTEXT, 0, to_wchar(txt).as_ptr() as winapi::LPARAM);
-		}
-	}
+    /// Sets the icons buttons refer to by index. All icons are drawn at the
+    /// size of the first one.
+    pub fn set_icons(&self, images: &[&Image]) {
+        let TB_SETIMAGELIST = 0x0430;
+        let TB_AUTOSIZE = 0x0421;
 
-    fn get_text(&self) -> String {
-        let size = self.get_text_length() + 1; //Win32 adds the NULL. So we need one extra space
-        let mut v : Vec<u16> = Vec::with_capacity(size as usize);
+        if images.is_empty() {
+            return;
+        }
 
-		unsafe {
-            v.set_len((size - 1) as usize);
-			user32::SendMessageW(self.get_hwnd(), winapi::WM_GETTEXT, size as winapi::WPARAM, v.as_ptr() as winapi::LPARAM);
-		}
+        unsafe {
+            let list = create_image_list(images);
+            let old = user32::SendMessageW(self.window, TB_SETIMAGELIST, 0,
+                list as winapi::LPARAM) as winapi::HIMAGELIST;
 
-        return String::from_utf16(&v[..]).unwrap();
-	}
+            if !old.is_null() {
+                comctl32::ImageList_Destroy(old);
+            }
 
-    fn attach_event_handler(&self, handler: &WindowEventHandler) {
-        unsafe {
-            let raw_obj: raw::TraitObject = mem::transmute(handler);
+            //The parent destroys the list once the toolbar is gone
+            set_prop(user32::GetParent(self.window), "cwnd.toolbarimages", list as winapi::HANDLE);
 
-            let prop = to_wchar("cwnd.data");
-            user32::SetPropW(self.get_hwnd(), prop.as_ptr(), raw_obj.data as winapi::HANDLE);
-            let prop = to_wchar("cwnd.vtable");
-            user32::SetPropW(self.get_hwnd(), prop.as_ptr(), raw_obj.vtable as winapi::HANDLE);
+            user32::SendMessageW(self.window, TB_AUTOSIZE, 0, 0);
         }
+
+        layout_bars(unsafe { user32::GetParent(self.window) });
     }
 
-    fn detach_event_handler(&mut self) {
+    fn add(&self, id: u16, icon: i32, tooltip: &str, style: u8) {
+        let TB_ADDBUTTONSW = 0x0444;
+        let TB_AUTOSIZE = 0x0421;
+        let TBSTATE_ENABLED = 0x04;
+        let tooltip = to_wchar(tooltip);
+
         unsafe {
-            let prop = to_wchar("cwnd.data");
-            user32::RemovePropW(self.get_hwnd(), prop.as_ptr());
-            let prop = to_wchar("cwnd.vtable");
-            user32::RemovePropW(self.get_hwnd(), prop.as_ptr());
+            let mut button: winapi::TBBUTTON = mem::zeroed();
+
+            button.iBitmap = icon;
+            button.idCommand = id as i32;
+            button.fsState = TBSTATE_ENABLED;
+            button.fsStyle = style;
+            button.iString = tooltip.as_ptr() as winapi::INT_PTR;
+
+            user32::SendMessageW(self.window, TB_ADDBUTTONSW, 1, &button as *const winapi::TBBUTTON as winapi::LPARAM);
+            user32::SendMessageW(self.window, TB_AUTOSIZE, 0, 0);
         }
     }
 
-    fn get_event_handler(&self) -> Option<&mut WindowEventHandler> {
-        unsafe {
-            let prop = to_wchar("cwnd.data");
-            let data = user32::GetPropW(self.get_hwnd(), prop.as_ptr());
-            let prop = to_wchar("cwnd.vtable");
-            let vtable = user32::GetPropW(self.get_hwnd(), prop.as_ptr());
+    /// Adds a push button showing the icon with the given index.
+    pub fn add_button(&self, id: u16, icon: i32, tooltip: &str) {
+        let BTNS_BUTTON = 0x00;
 
-            if data == ptr::null_mut() || vtable == ptr::null_mut() {
-                println!("wnd_proc Can not find attached data for HWND: {}.", self.get_hwnd() as i32);
-                return None;
-            }
+        self.add(id, icon, tooltip, BTNS_BUTTON);
+    }
 
-            let synthesized: &mut WindowEventHandler =
-                mem::transmute(raw::TraitObject {
-                 data: data as *const _ as *mut (),
-                 vtable: vtable as *const _ as *mut ()
-                });
+    /// Adds a button that stays pressed until clicked again.
+    pub fn add_toggle_button(&self, id: u16, icon: i32, tooltip: &str) {
+        let BTNS_CHECK = 0x02;
 
-            return Some(synthesized);
-        }
+        self.add(id, icon, tooltip, BTNS_CHECK);
     }
 
-    fn set_timer(&mut self, id : usize, interval : usize) {
+    pub fn add_separator(&self) {
+        let BTNS_SEP = 0x01;
+
+        self.add(0, 0, "", BTNS_SEP);
+    }
+
+    pub fn get_button_count(&self) -> i32 {
+        let TB_BUTTONCOUNT = 0x0418;
+
         unsafe {
-            user32::SetTimer(self.get_hwnd(), id as winapi::UINT_PTR, interval as winapi::UINT, Some(timer_proc));
+            return user32::SendMessageW(self.window, TB_BUTTONCOUNT, 0, 0) as i32;
         }
     }
-    fn kill_timer(&mut self, id : usize) {
+
+    pub fn is_checked(&self, id: u16) -> bool {
+        let TB_ISBUTTONCHECKED = 0x040A;
+
         unsafe {
-            user32::KillTimer(self.get_hwnd(), id as winapi::UINT_PTR);
+            return user32::SendMessageW(self.window, TB_ISBUTTONCHECKED, id as winapi::WPARAM, 0) != 0;
         }
     }
-    fn message_box(&self, msg: &str) {
-        let msg = to_wchar(msg);
-        let title = to_wchar("Message");
+
+    pub fn set_checked(&self, id: u16, checked: bool) {
+        let TB_CHECKBUTTON = 0x0402;
 
         unsafe {
-    	       user32::MessageBoxW(self.get_hwnd(), msg.as_ptr(), title.as_ptr(), winapi::MB_OK);
+            user32::SendMessageW(self.window, TB_CHECKBUTTON, id as winapi::WPARAM, checked as winapi::LPARAM);
         }
     }
-    fn error_box(&self, msg: &str) {
-        let msg = to_wchar(msg);
-        let title = to_wchar("Error");
+
+    pub fn set_enabled(&self, id: u16, enabled: bool) {
+        let TB_ENABLEBUTTON = 0x0401;
 
         unsafe {
-    	       user32::MessageBoxW(self.get_hwnd(), msg.as_ptr(), title.as_ptr(), winapi::MB_OK | winapi::MB_ICONERROR);
+            user32::SendMessageW(self.window, TB_ENABLEBUTTON, id as winapi::WPARAM, enabled as winapi::LPARAM);
         }
     }
+}
 
-    fn question_box(&self, msg: &str) -> bool {
-        let msg = to_wchar(msg);
-        let title = to_wchar("Question");
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Orientation {
+    /// Panes side by side with a vertical divider between them.
+    Horizontal,
+    /// Panes stacked with a horizontal divider between them.
+    Vertical
+}
+
+//Event handler of a Splitter window. Lays out the panes and drags the
+//divider.
+struct SplitterState {
+    window: winapi::HWND,
+    orientation: Orientation,
+    first: Cell<winapi::HWND>,
+    second: Cell<winapi::HWND>,
+    ratio: Cell<f32>,
+    min_first: Cell<i32>,
+    min_second: Cell<i32>,
+    //Distance from the mouse to the start of the divider while dragging
+    drag_offset: Cell<Option<i32>>
+}
+
+const SPLITTER_BAR_SIZE: i32 = 5;
+
+impl SplitterState {
+    //Length along the split direction, excluding the divider.
+    fn available(&self) -> i32 {
+        let mut client = winapi::RECT {
+            top: 0, left: 0, right: 0, bottom: 0
+        };
 
         unsafe {
-    	       return user32::MessageBoxW(self.get_hwnd(), msg.as_ptr(), title.as_ptr(),
-                winapi::MB_YESNO | winapi::MB_ICONQUESTION) == 6; //winapi::IDYES
+            user32::GetClientRect(self.window, &mut client);
         }
+
+        let total = match self.orientation {
+            Orientation::Horizontal => client.right,
+            Orientation::Vertical => client.bottom
+        };
+
+        if total > SPLITTER_BAR_SIZE {total - SPLITTER_BAR_SIZE} else {0}
     }
 
-    fn confirm_box(&self, msg: &str) -> bool {
-        let msg = to_wchar(msg);
-        let title = to_wchar("Confirm");
+    //Start of the divider
+    fn position(&self) -> i32 {
+        let available = self.available();
+        let position = (available as f32 * self.ratio.get()).round() as i32;
+        let position = if position > available - self.min_second.get() {available - self.min_second.get()} else {position};
+
+        //The first pane wins when both minimums don't fit
+        if position < self.min_first.get() {self.min_first.get()} else {position}
+    }
+
+    fn is_on_bar(&self, x: i32, y: i32) -> bool {
+        let coordinate = if self.orientation == Orientation::Horizontal {x} else {y};
+        let position = self.position();
+
+        coordinate >= position && coordinate < position + SPLITTER_BAR_SIZE
+    }
+
+    fn layout(&self) {
+        let mut client = winapi::RECT {
+            top: 0, left: 0, right: 0, bottom: 0
+        };
 
         unsafe {
-    	       return user32::MessageBoxW(self.get_hwnd(), msg.as_ptr(), title.as_ptr(),
-                winapi::MB_OKCANCEL | winapi::MB_ICONINFORMATION) == 1; //winapi::IDOK
+            user32::GetClientRect(self.window, &mut client);
+        }
+
+        let position = self.position();
+        let rest = self.available() - position;
+        let (first, second) = (self.first.get(), self.second.get());
+
+        unsafe {
+            match self.orientation {
+                Orientation::Horizontal => {
+                    if !first.is_null() {
+                        user32::MoveWindow(first, 0, 0, position, client.bottom, 1);
+                    }
+                    if !second.is_null() {
+                        user32::MoveWindow(second, position + SPLITTER_BAR_SIZE, 0, rest, client.bottom, 1);
+                    }
+                },
+                Orientation::Vertical => {
+                    if !first.is_null() {
+                        user32::MoveWindow(first, 0, 0, client.right, position, 1);
+                    }
+                    if !second.is_null() {
+                        user32::MoveWindow(second, 0, position + SPLITTER_BAR_SIZE, client.right, rest, 1);
+                    }
+                }
+            }
         }
     }
 }
 
-impl Window for winapi::HWND {
-	fn get_hwnd(&self) -> winapi::HWND {
-		return *self;
-	}
+impl WindowEventHandler for SplitterState {
+    fn on_size(&mut self, width: i32, height: i32) {
+        self.layout();
+    }
+
+    fn on_left_mouse_down(&mut self, x: i32, y: i32) {
+        if self.is_on_bar(x, y) {
+            let coordinate = if self.orientation == Orientation::Horizontal {x} else {y};
+
+            self.drag_offset.set(Some(coordinate - self.position()));
+
+            unsafe {
+                user32::SetCapture(self.window);
+            }
+        }
+    }
+
+    fn on_mouse_move(&mut self, x: i32, y: i32) {
+        if let Some(offset) = self.drag_offset.get() {
+            let available = self.available();
+            let coordinate = if self.orientation == Orientation::Horizontal {x} else {y};
+
+            if available > 0 {
+                let ratio = (coordinate - offset) as f32 / available as f32;
+
+                self.ratio.set(ratio.max(0.0).min(1.0));
+                self.layout();
+            }
+        }
+    }
+
+    fn on_left_mouse_up(&mut self, x: i32, y: i32) {
+        if self.drag_offset.get().is_some() {
+            unsafe {
+                user32::ReleaseCapture();
+            }
+        }
+    }
+
+    fn on_event(&mut self, window: winapi::HWND, message : winapi::UINT,  w_param : winapi::WPARAM, l_param : winapi::LPARAM) -> bool {
+        let WM_CAPTURECHANGED = 0x0215;
+        let IDC_SIZEWE = 32644;
+        let IDC_SIZENS = 32645;
+
+        match message {
+            winapi::WM_SETCURSOR => {
+                let mut point = winapi::POINT {
+                    x: 0,
+                    y: 0
+                };
+
+                unsafe {
+                    user32::GetCursorPos(&mut point);
+                    user32::ScreenToClient(window, &mut point);
+
+                    if !self.is_on_bar(point.x, point.y) {
+                        return false;
+                    }
+
+                    let cursor = if self.orientation == Orientation::Horizontal {IDC_SIZEWE} else {IDC_SIZENS};
+
+                    user32::SetCursor(user32::LoadCursorW(ptr::null_mut(), cursor as winapi::LPCWSTR));
+                }
+            },
+            //Capture was released or taken away
+            _ if message == WM_CAPTURECHANGED => {
+                self.drag_offset.set(None);
+            },
+            _ => {
+                //Controls in the panes report to the splitter
+                if forward_notification(window, message, w_param, l_param) {
+                    return true;
+                }
+
+                return self.dispatch_event(window, message, w_param, l_param);
+            }
+        }
+
+        return true;
+    }
 }
 
-pub struct Button {
-    window : winapi::HWND
+/// A container with two panes and a divider the user can drag to resize
+/// them. Create the pane controls with the splitter as their parent, then
+/// call set_panes(). A pane can be another Splitter for nested layouts.
+/// Notifications from controls in the panes are passed on to the parent.
+pub struct Splitter {
+    window : winapi::HWND,
+    state : Box<SplitterState>
 }
 
-impl Window for Button {
+impl Window for Splitter {
     fn get_hwnd(&self) -> winapi::HWND {
 		return self.window;
 	}
 }
 
-impl Button {
-    pub fn new(parent: &Window, id: u16, title: &str, x: i32, y: i32, width: i32, height: i32) -> Button {
+impl Splitter {
+    pub fn new(parent: &Window, x: i32, y: i32, width: i32, height: i32, orientation: Orientation) -> Splitter {
         let wnd = WindowBuilder::new()
-            .title(title)
-            .class_name("BUTTON")
-            .style(winapi::WS_VISIBLE | winapi::WS_TABSTOP | winapi::WS_CHILD | winapi::BS_PUSHBUTTON)
-            .position(x, y)
-            .size(width, height)
+            .class_name("HOWL")
             .parent(parent.get_hwnd())
-            .id(id)
+            .style(winapi::WS_VISIBLE | winapi::WS_CHILD | winapi::WS_CLIPCHILDREN)
+            .size(width, height)
+            .position(x, y)
             .create();
-        Button {
-            window: wnd
+        let state = Box::new(SplitterState {
+            window: wnd,
+            orientation: orientation,
+            first: Cell::new(ptr::null_mut()),
+            second: Cell::new(ptr::null_mut()),
+            ratio: Cell::new(0.5),
+            min_first: Cell::new(0),
+            min_second: Cell::new(0),
+            drag_offset: Cell::new(None)
+        });
+
+        wnd.attach_event_handler(&*state);
+
+        Splitter {
+            window: wnd,
+            state: state
+        }
+    }
+
+    pub fn set_panes(&self, first: &Window, second: &Window) {
+        unsafe {
+            user32::SetParent(first.get_hwnd(), self.window);
+            user32::SetParent(second.get_hwnd(), self.window);
         }
+
+        self.state.first.set(first.get_hwnd());
+        self.state.second.set(second.get_hwnd());
+        self.state.layout();
+    }
+
+    /// Sets the share of the space given to the first pane, from 0.0 to
+    /// 1.0. Save get_ratio() to restore the layout later.
+    pub fn set_ratio(&self, ratio: f32) {
+        self.state.ratio.set(ratio.max(0.0).min(1.0));
+        self.state.layout();
+    }
+
+    pub fn get_ratio(&self) -> f32 {
+        self.state.ratio.get()
+    }
+
+    /// The divider can not be dragged closer than these sizes to either
+    /// edge.
+    pub fn set_min_sizes(&self, first: i32, second: i32) {
+        self.state.min_first.set(first);
+        self.state.min_second.set(second);
+        self.state.layout();
     }
 }
 
-pub struct Edit {
-    window : winapi::HWND
+impl Drop for Splitter {
+    fn drop(&mut self) {
+        //The handler is about to be freed
+        let mut window = self.window;
+
+        window.detach_event_handler();
+    }
 }
 
-impl Window for Edit {
-    fn get_hwnd(&self) -> winapi::HWND {
-		return self.window;
-	}
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ScrollBar {
+    Horizontal = 0,
+    Vertical = 1
 }
 
-impl Edit {
-    pub fn new(parent: &Window, x: i32, y: i32, width: i32, height: i32, multi_line:bool) -> Edit {
-        let ES_LEFT             = 0x0000;
-        let ES_CENTER           = 0x0001;
-        let ES_RIGHT            = 0x0002;
-        let ES_MULTILINE        = 0x0004;
-        let ES_UPPERCASE        = 0x0008;
-        let ES_LOWERCASE        = 0x0010;
-        let ES_PASSWORD         = 0x0020;
-        let ES_AUTOVSCROLL      = 0x0040;
-        let ES_AUTOHSCROLL      = 0x0080;
-        let ES_NOHIDESEL        = 0x0100;
-        let ES_OEMCONVERT       = 0x0400;
-        let ES_READONLY         = 0x0800;
-        let ES_WANTRETURN       = 0x1000;
+//Event handler of the content window of a ScrollView. Sends notifications
+//and wheel turns from the content up to the ScrollView.
+struct ScrollContentHandler;
 
-        let mut style : winapi::DWORD  = winapi::WS_CHILD | winapi::WS_VISIBLE;
+impl WindowEventHandler for ScrollContentHandler {
+    fn on_event(&mut self, window: winapi::HWND, message : winapi::UINT,  w_param : winapi::WPARAM, l_param : winapi::LPARAM) -> bool {
+        if message == winapi::WM_MOUSEWHEEL {
+            unsafe {
+                user32::SendMessageW(user32::GetParent(window), message, w_param, l_param);
+            }
 
-    	if multi_line {
-    		style = style | winapi::WS_VSCROLL |
-                ES_WANTRETURN | ES_LEFT | ES_MULTILINE | ES_AUTOVSCROLL;
+            return true;
         }
 
-        let wnd = WindowBuilder::new()
-            .class_name("EDIT")
-            .style(style)
-            .position(x, y)
-            .size(width, height)
-            .parent(parent.get_hwnd())
-            .create();
-        Edit {
-            window: wnd
+        if forward_notification(window, message, w_param, l_param) {
+            return true;
         }
+
+        return self.dispatch_event(window, message, w_param, l_param);
     }
 
-    pub fn set_read_only(&self, read_only : bool) {
-        let EM_SETREADONLY = 0x00CF;
+    fn on_size(&mut self, width: i32, height: i32) {
+    }
+}
+
+//Event handler of a ScrollView window.
+struct ScrollViewState {
+    window: winapi::HWND,
+    content: winapi::HWND,
+    content_handler: ScrollContentHandler,
+    width: Cell<i32>,
+    height: Cell<i32>
+}
+
+const SCROLL_LINE_SIZE: i32 = 20;
+
+impl ScrollViewState {
+    fn update_scroll_bars(&self) {
+        let mut client = winapi::RECT {
+            top: 0, left: 0, right: 0, bottom: 0
+        };
 
         unsafe {
-            user32::SendMessageW(self.window, EM_SETREADONLY, if read_only {1} else {0}, 0);
+            user32::GetClientRect(self.window, &mut client);
         }
+
+        self.window.set_scroll_range(ScrollBar::Horizontal, self.width.get(), client.right);
+        self.window.set_scroll_range(ScrollBar::Vertical, self.height.get(), client.bottom);
+        self.move_content();
     }
 
-    pub fn append_text(&self, txt : &str) {
-    	let prev_len = self.get_text_length();
-        let txt = to_wchar(txt);
-        let EM_SETSEL               = 0x00B1;
-        let EM_REPLACESEL           = 0x00C2;
+    fn move_content(&self) {
+        let x = self.window.get_scroll_position(ScrollBar::Horizontal);
+        let y = self.window.get_scroll_position(ScrollBar::Vertical);
 
         unsafe {
-        	user32::SendMessageW(self.window, EM_SETSEL, prev_len as winapi::WPARAM, prev_len as winapi::LPARAM);
-        	user32::SendMessageW(self.window, EM_REPLACESEL, 0, txt.as_ptr() as winapi::LPARAM);
+            user32::MoveWindow(self.content, -x, -y, self.width.get(), self.height.get(), 1);
         }
     }
 }
 
-pub struct ListBox {
-    window : winapi::HWND
+impl WindowEventHandler for ScrollViewState {
+    fn on_size(&mut self, width: i32, height: i32) {
+        self.update_scroll_bars();
+    }
+
+    fn on_scroll(&mut self, bar: ScrollBar, request: u16, position: i32) {
+        self.window.apply_scroll_request(bar, request, SCROLL_LINE_SIZE);
+        self.move_content();
+    }
+
+    fn on_mouse_wheel(&mut self, delta: i32, x: i32, y: i32) -> bool {
+        let WHEEL_DELTA = 120;
+        let lines = 3;
+        let position = self.window.get_scroll_position(ScrollBar::Vertical);
+
+        self.window.set_scroll_position(ScrollBar::Vertical, position - delta * lines * SCROLL_LINE_SIZE / WHEEL_DELTA);
+        self.move_content();
+
+        true
+    }
+
+    fn on_event(&mut self, window: winapi::HWND, message : winapi::UINT,  w_param : winapi::WPARAM, l_param : winapi::LPARAM) -> bool {
+        if forward_notification(window, message, w_param, l_param) {
+            return true;
+        }
+
+        return self.dispatch_event(window, message, w_param, l_param);
+    }
 }
 
-impl Window for ListBox {
+/// A container that scrolls content larger than itself. Create controls
+/// with get_content() as their parent and give the content its full size
+/// with set_virtual_size(). Scroll bars appear as needed and the mouse
+/// wheel scrolls vertically. Notifications from the controls are passed
+/// on to the parent of the ScrollView.
+pub struct ScrollView {
+    window : winapi::HWND,
+    content : Canvas,
+    state : Box<ScrollViewState>
+}
+
+impl Window for ScrollView {
     fn get_hwnd(&self) -> winapi::HWND {
 		return self.window;
 	}
 }
 
-impl ListBox {
-    pub fn new(parent: &Window, id: u16, x: i32, y: i32, width: i32, height: i32) -> ListBox {
-        let LBS_NOTIFY = 1;
-        let LBS_HASSTRINGS = 64;
-
+impl ScrollView {
+    pub fn new(parent: &Window, x: i32, y: i32, width: i32, height: i32) -> ScrollView {
         let wnd = WindowBuilder::new()
-            .class_name("LISTBOX")
-            .style(winapi::WS_VISIBLE | winapi::WS_VSCROLL | winapi::WS_CHILD | LBS_NOTIFY | LBS_HASSTRINGS)
-            .position(x, y)
-            .size(width, height)
+            .class_name("HOWL")
             .parent(parent.get_hwnd())
-            .id(id)
+            .style(winapi::WS_VISIBLE | winapi::WS_CHILD | winapi::WS_CLIPCHILDREN | winapi::WS_HSCROLL | winapi::WS_VSCROLL)
+            .size(width, height)
+            .position(x, y)
             .create();
-        ListBox {
-            window: wnd
+        let content = Canvas::new(&wnd, 0, 0, width, height);
+        let state = Box::new(ScrollViewState {
+            window: wnd,
+            content: content.get_hwnd(),
+            content_handler: ScrollContentHandler,
+            width: Cell::new(width),
+            height: Cell::new(height)
+        });
+
+        wnd.attach_event_handler(&*state);
+        content.attach_event_handler(&state.content_handler);
+        state.update_scroll_bars();
+
+        ScrollView {
+            window: wnd,
+            content: content,
+            state: state
         }
     }
 
-    pub fn add_item(&self, val : &str) {
-        let val = to_wchar(val);
-        let LB_ADDSTRING = 384;
-
-        unsafe {
-            user32::SendMessageW(self.window, LB_ADDSTRING, 0, val.as_ptr() as winapi::LPARAM);
-        }
+    /// The window that holds the scrolled controls.
+    pub fn get_content(&self) -> &Canvas {
+        &self.content
     }
 
-    pub fn delete_item(&self, idx : u32) {
-        let LB_DELETESTRING = 386;
-
-        unsafe {
-            user32::SendMessageW(self.window, LB_DELETESTRING, idx, 0);
-        }
+    /// Sets the full size of the content.
+    pub fn set_virtual_size(&self, width: i32, height: i32) {
+        self.state.width.set(width);
+        self.state.height.set(height);
+        self.state.update_scroll_bars();
     }
-    pub fn get_item_count(&self) -> i32 {
-        let LB_GETCOUNT = 395;
 
-        unsafe {
-    	       return user32::SendMessageW(self.window, LB_GETCOUNT, 0, 0);
-        }
+    /// Scrolls so that the point of the content is at the top left corner,
+    /// as far as the content size allows.
+    pub fn scroll_to(&self, x: i32, y: i32) {
+        self.window.set_scroll_position(ScrollBar::Horizontal, x);
+        self.window.set_scroll_position(ScrollBar::Vertical, y);
+        self.state.move_content();
     }
-    pub fn clear(&self) {
-        let LB_RESETCONTENT = 388;
 
-        unsafe {
-            user32::SendMessageW(self.window, LB_RESETCONTENT, 0, 0);
-        }
+    pub fn get_scroll_offset(&self) -> (i32, i32) {
+        (self.window.get_scroll_position(ScrollBar::Horizontal), self.window.get_scroll_position(ScrollBar::Vertical))
     }
-    pub fn get_sel(&self) -> i32 {
-        let LB_GETCURSEL = 392;
+}
 
-        unsafe {
-               return user32::SendMessageW(self.window, LB_GETCURSEL, 0, 0);
-        }
-    }
-    pub fn set_sel(&self, idx : u32) {
-        let LB_SETCURSEL = 390;
+impl Drop for ScrollView {
+    fn drop(&mut self) {
+        //The handlers are about to be freed
+        let mut window = self.window;
+        let mut content = self.content.get_hwnd();
 
-        unsafe {
-            user32::SendMessageW(self.window, LB_SETCURSEL, idx, 0);
-        }
+        window.detach_event_handler();
+        content.detach_event_handler();
     }
 }
 
@@ -668,6 +6553,18 @@ impl Canvas {
             window: wnd
         }
     }
+
+    /// When enabled, on_paint() draws into an off-screen bitmap that is
+    /// copied to the screen in one go, and background erasing is
+    /// suppressed. This removes flicker from complex or animated scenes.
+    pub fn set_double_buffered(&self, double_buffered: bool) {
+        set_prop(self.window, "cwnd.doublebuffer", if double_buffered {1 as winapi::HANDLE} else {ptr::null_mut()});
+        self.invalidate();
+    }
+
+    pub fn is_double_buffered(&self) -> bool {
+        is_double_buffered(self.window)
+    }
 }
 
 pub struct Frame {
@@ -695,11 +6592,494 @@ impl Frame {
     }
 }
 
+/// Drawing surface handed to WindowEventHandler::on_paint(). It is only
+/// valid for the duration of the paint call.
+pub struct Graphics {
+    dc: winapi::HDC
+}
+
+impl Graphics {
+    pub fn get_dc(&self) -> winapi::HDC {
+        return self.dc;
+    }
+
+    /// Draws text inside the rectangle using the DC's current text color.
+    pub fn draw_text(&self, text: &str, font: &Font, rect: &winapi::RECT, format: &TextFormat) {
+        let TRANSPARENT = 1;
+        let text: Vec<u16> = OsStr::new(text).encode_wide().collect();
+        let mut rect = *rect;
+
+        unsafe {
+            let old_font = gdi32::SelectObject(self.dc, font.get_handle() as winapi::HGDIOBJ);
+            let old_mode = gdi32::SetBkMode(self.dc, TRANSPARENT);
+
+            user32::DrawTextW(self.dc, text.as_ptr(), text.len() as i32, &mut rect, format.flags());
+
+            gdi32::SetBkMode(self.dc, old_mode);
+            gdi32::SelectObject(self.dc, old_font);
+        }
+    }
+
+    /// Returns the size the text takes up when drawn with the font. If
+    /// max_width is given the text is wrapped to that width.
+    pub fn measure_text(&self, text: &str, font: &Font, max_width: Option<i32>) -> winapi::SIZE {
+        let DT_CALCRECT = 0x0400;
+        let text: Vec<u16> = OsStr::new(text).encode_wide().collect();
+        let mut format = TextFormat::new();
+        let mut rect = winapi::RECT {
+            top: 0, left: 0, right: 0, bottom: 0
+        };
+
+        if let Some(max_width) = max_width {
+            format.wrap = true;
+            rect.right = max_width;
+        }
+
+        unsafe {
+            let old_font = gdi32::SelectObject(self.dc, font.get_handle() as winapi::HGDIOBJ);
+
+            user32::DrawTextW(self.dc, text.as_ptr(), text.len() as i32, &mut rect, format.flags() | DT_CALCRECT);
+
+            gdi32::SelectObject(self.dc, old_font);
+        }
+
+        winapi::SIZE {
+            cx: rect.right - rect.left,
+            cy: rect.bottom - rect.top
+        }
+    }
+
+    /// Draws the image at its natural size, honoring its alpha channel.
+    pub fn draw_image(&self, image: &Image, x: i32, y: i32) {
+        self.draw_image_blended(image, x, y, image.width(), image.height(), 255);
+    }
+
+    /// Draws the image stretched to the given size.
+    pub fn draw_image_scaled(&self, image: &Image, x: i32, y: i32, width: i32, height: i32) {
+        self.draw_image_blended(image, x, y, width, height, 255);
+    }
+
+    /// Draws the image stretched to the given size. opacity is applied on
+    /// top of the image's own alpha channel. 0 is invisible, 255 is opaque.
+    pub fn draw_image_blended(&self, image: &Image, x: i32, y: i32, width: i32, height: i32, opacity: u8) {
+        let AC_SRC_OVER = 0x00;
+        let AC_SRC_ALPHA = 0x01;
+
+        let blend = winapi::BLENDFUNCTION {
+            BlendOp: AC_SRC_OVER,
+            BlendFlags: 0,
+            SourceConstantAlpha: opacity,
+            AlphaFormat: AC_SRC_ALPHA
+        };
+
+        unsafe {
+            let image_dc = gdi32::CreateCompatibleDC(self.dc);
+            let old_bitmap = gdi32::SelectObject(image_dc, image.get_bitmap() as winapi::HGDIOBJ);
+
+            GdiAlphaBlend(self.dc, x, y, width, height,
+                image_dc, 0, 0, image.width(), image.height(), blend);
+
+            gdi32::SelectObject(image_dc, old_bitmap);
+            gdi32::DeleteDC(image_dc);
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    /// 255 is opaque. Only used where blending is supported.
+    pub a: u8
+}
+
+impl Color {
+    pub const BLACK: Color = Color { r: 0, g: 0, b: 0, a: 255 };
+    pub const WHITE: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+    pub const RED: Color = Color { r: 255, g: 0, b: 0, a: 255 };
+    pub const GREEN: Color = Color { r: 0, g: 128, b: 0, a: 255 };
+    pub const BLUE: Color = Color { r: 0, g: 0, b: 255, a: 255 };
+    pub const GRAY: Color = Color { r: 128, g: 128, b: 128, a: 255 };
+    pub const TRANSPARENT: Color = Color { r: 0, g: 0, b: 0, a: 0 };
+
+    pub fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color::rgba(r, g, b, 255)
+    }
+
+    pub fn rgba(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color {
+            r: r,
+            g: g,
+            b: b,
+            a: a
+        }
+    }
+
+    /// Parses "#rgb", "#rrggbb" or "#rrggbbaa". The leading # is optional.
+    pub fn from_hex(hex: &str) -> Option<Color> {
+        let hex = hex.trim_start_matches('#');
+
+        if !hex.chars().all(|c| c.is_digit(16)) {
+            return None;
+        }
+
+        let channel = |i: usize| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok();
+
+        match hex.len() {
+            3 => {
+                let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).ok().map(|d| d * 17);
+
+                Some(Color::rgb(digit(0)?, digit(1)?, digit(2)?))
+            },
+            6 => Some(Color::rgb(channel(0)?, channel(1)?, channel(2)?)),
+            8 => Some(Color::rgba(channel(0)?, channel(1)?, channel(2)?, channel(3)?)),
+            _ => None
+        }
+    }
+
+    /// Looks up one of the basic CSS color names. Case is ignored.
+    pub fn from_name(name: &str) -> Option<Color> {
+        let color = match name.to_lowercase().as_str() {
+            "black" => Color::BLACK,
+            "white" => Color::WHITE,
+            "red" => Color::RED,
+            "green" => Color::GREEN,
+            "blue" => Color::BLUE,
+            "gray" | "grey" => Color::GRAY,
+            "silver" => Color::rgb(192, 192, 192),
+            "maroon" => Color::rgb(128, 0, 0),
+            "yellow" => Color::rgb(255, 255, 0),
+            "olive" => Color::rgb(128, 128, 0),
+            "lime" => Color::rgb(0, 255, 0),
+            "aqua" | "cyan" => Color::rgb(0, 255, 255),
+            "teal" => Color::rgb(0, 128, 128),
+            "navy" => Color::rgb(0, 0, 128),
+            "fuchsia" | "magenta" => Color::rgb(255, 0, 255),
+            "purple" => Color::rgb(128, 0, 128),
+            "orange" => Color::rgb(255, 165, 0),
+            "transparent" => Color::TRANSPARENT,
+            _ => return None
+        };
+
+        Some(color)
+    }
+
+    pub fn to_colorref(&self) -> winapi::COLORREF {
+        (self.r as winapi::COLORREF) | ((self.g as winapi::COLORREF) << 8) | ((self.b as winapi::COLORREF) << 16)
+    }
+
+    pub fn from_colorref(color: winapi::COLORREF) -> Color {
+        Color::rgb((color & 0xFF) as u8, ((color >> 8) & 0xFF) as u8, ((color >> 16) & 0xFF) as u8)
+    }
+}
+
+pub struct Font {
+    font: winapi::HFONT
+}
+
+impl Font {
+    pub fn get_handle(&self) -> winapi::HFONT {
+        return self.font;
+    }
+}
+
+impl Drop for Font {
+    fn drop(&mut self) {
+        unsafe {
+            gdi32::DeleteObject(self.font as winapi::HGDIOBJ);
+        }
+    }
+}
+
+pub struct FontBuilder<'a> {
+    family: &'a str,
+    size: i32,
+    weight: i32,
+    italic: bool,
+    underline: bool
+}
+
+impl <'a> FontBuilder<'a> {
+    pub fn new() -> FontBuilder<'a> {
+        FontBuilder {
+            family: "Segoe UI",
+            size: 9,
+            weight: 400,
+            italic: false,
+            underline: false
+        }
+    }
+    pub fn family(&'a mut self, family: &'a str) -> &mut FontBuilder {
+        self.family = family;
+
+        self
+    }
+    /// Size in points.
+    pub fn size(&'a mut self, size: i32) -> &mut FontBuilder {
+        self.size = size;
+
+        self
+    }
+    /// Weight from 100 (thin) to 900 (black). 400 is normal, 700 is bold.
+    pub fn weight(&'a mut self, weight: i32) -> &mut FontBuilder {
+        self.weight = weight;
+
+        self
+    }
+    pub fn bold(&'a mut self) -> &mut FontBuilder {
+        self.weight(700)
+    }
+    pub fn italic(&'a mut self, italic: bool) -> &mut FontBuilder {
+        self.italic = italic;
+
+        self
+    }
+    pub fn underline(&'a mut self, underline: bool) -> &mut FontBuilder {
+        self.underline = underline;
+
+        self
+    }
+
+    pub fn create(&self) -> Font {
+        let LOGPIXELSY = 90;
+        let DEFAULT_CHARSET = 1;
+        let OUT_DEFAULT_PRECIS = 0;
+        let CLIP_DEFAULT_PRECIS = 0;
+        let CLEARTYPE_QUALITY = 5;
+        let DEFAULT_PITCH = 0;
+
+        unsafe {
+            let screen = user32::GetDC(ptr::null_mut());
+            let dpi = gdi32::GetDeviceCaps(screen, LOGPIXELSY);
+            user32::ReleaseDC(ptr::null_mut(), screen);
+
+            //Negative height asks for the character height, which is what
+            //point sizes measure
+            let height = -kernel32::MulDiv(self.size, dpi, 72);
+
+            let font = gdi32::CreateFontW(height, 0, 0, 0, self.weight,
+                self.italic as winapi::DWORD, self.underline as winapi::DWORD, 0,
+                DEFAULT_CHARSET, OUT_DEFAULT_PRECIS, CLIP_DEFAULT_PRECIS,
+                CLEARTYPE_QUALITY, DEFAULT_PITCH, to_wchar(self.family).as_ptr());
+
+            if font.is_null() {
+                panic!("CreateFontW error: {}", kernel32::GetLastError());
+            }
+
+            Font {
+                font: font
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right
+}
+
+/// Layout options for Graphics::draw_text().
+#[derive(Clone, Copy, Debug)]
+pub struct TextFormat {
+    pub align: TextAlign,
+    /// Break lines at word boundaries to fit the width. Otherwise the
+    /// text is drawn on one line, vertically centered.
+    pub wrap: bool,
+    /// Replace the end of text that does not fit with "...".
+    pub ellipsis: bool
+}
+
+impl TextFormat {
+    pub fn new() -> TextFormat {
+        TextFormat {
+            align: TextAlign::Left,
+            wrap: false,
+            ellipsis: false
+        }
+    }
+
+    fn flags(&self) -> winapi::UINT {
+        let DT_LEFT = 0x0000;
+        let DT_CENTER = 0x0001;
+        let DT_RIGHT = 0x0002;
+        let DT_VCENTER = 0x0004;
+        let DT_WORDBREAK = 0x0010;
+        let DT_SINGLELINE = 0x0020;
+        let DT_NOPREFIX = 0x0800;
+        let DT_END_ELLIPSIS = 0x8000;
+
+        let mut flags = DT_NOPREFIX | match self.align {
+            TextAlign::Left => DT_LEFT,
+            TextAlign::Center => DT_CENTER,
+            TextAlign::Right => DT_RIGHT
+        };
+
+        if self.wrap {
+            flags = flags | DT_WORDBREAK;
+        } else {
+            flags = flags | DT_SINGLELINE | DT_VCENTER;
+        }
+
+        if self.ellipsis {
+            flags = flags | DT_END_ELLIPSIS;
+        }
+
+        flags
+    }
+}
+
+/// A bitmap image decoded from PNG or BMP data. JPEG is supported when
+/// the jpeg feature is enabled.
+pub struct Image {
+    width: i32,
+    height: i32,
+    //Straight RGBA, kept around for scaling
+    pixels: Vec<u8>,
+    //Top-down 32 bit DIB section with premultiplied BGRA, as AlphaBlend wants
+    bitmap: winapi::HBITMAP
+}
+
+impl Image {
+    pub fn from_file(path: &str) -> Result<Image, String> {
+        match image::open(path) {
+            Ok(decoded) => {
+                Image::from_decoded(decoded)
+            },
+            Err(e) => Err(format!("Can not load image {}: {}", path, e))
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Image, String> {
+        match image::load_from_memory(bytes) {
+            Ok(decoded) => {
+                Image::from_decoded(decoded)
+            },
+            Err(e) => Err(format!("Can not decode image: {}", e))
+        }
+    }
+
+    fn from_decoded(decoded: image::DynamicImage) -> Result<Image, String> {
+        let decoded = decoded.to_rgba8();
+        let (width, height) = decoded.dimensions();
+
+        if width > i32::max_value() as u32 || height > i32::max_value() as u32 {
+            return Err(format!("Image of {}x{} pixels is too large.", width, height));
+        }
+
+        Image::from_rgba(width as i32, height as i32, decoded.into_raw())
+    }
+
+    /// Creates an image from raw RGBA pixels, 4 bytes per pixel, rows top
+    /// to bottom. Fails if pixels does not hold exactly that much data.
+    pub fn from_rgba(width: i32, height: i32, pixels: Vec<u8>) -> Result<Image, String> {
+        if width <= 0 || height <= 0 {
+            return Err(format!("Invalid image size {}x{}.", width, height));
+        }
+
+        let size = (width as usize).checked_mul(height as usize)
+            .and_then(|size| size.checked_mul(4));
+
+        match size {
+            Some(size) if size == pixels.len() => {},
+            Some(size) => return Err(format!("Expected {} bytes of RGBA data, got {}.", size, pixels.len())),
+            None => return Err(format!("Image of {}x{} pixels is too large.", width, height))
+        }
+
+        let BI_RGB = 0;
+        let DIB_RGB_COLORS = 0;
+
+        let info = winapi::BITMAPINFO {
+            bmiHeader: winapi::BITMAPINFOHEADER {
+                biSize: mem::size_of::<winapi::BITMAPINFOHEADER>() as winapi::DWORD,
+                biWidth: width,
+                biHeight: -height, //Negative for top-down rows
+                biPlanes: 1,
+                biBitCount: 32,
+                biCompression: BI_RGB,
+                biSizeImage: 0,
+                biXPelsPerMeter: 0,
+                biYPelsPerMeter: 0,
+                biClrUsed: 0,
+                biClrImportant: 0
+            },
+            bmiColors: []
+        };
+
+        unsafe {
+            let mut bits: *mut libc::c_void = ptr::null_mut();
+            let bitmap = gdi32::CreateDIBSection(ptr::null_mut(), &info, DIB_RGB_COLORS,
+                &mut bits, ptr::null_mut(), 0);
+
+            if bitmap.is_null() {
+                return Err(format!("CreateDIBSection error: {}", kernel32::GetLastError()));
+            }
+
+            let bits = std::slice::from_raw_parts_mut(bits as *mut u8, pixels.len());
+
+            for (dest, src) in bits.chunks_mut(4).zip(pixels.chunks(4)) {
+                let alpha = src[3] as u32;
+
+                dest[0] = (src[2] as u32 * alpha / 255) as u8;
+                dest[1] = (src[1] as u32 * alpha / 255) as u8;
+                dest[2] = (src[0] as u32 * alpha / 255) as u8;
+                dest[3] = src[3];
+            }
+
+            Ok(Image {
+                width: width,
+                height: height,
+                pixels: pixels,
+                bitmap: bitmap
+            })
+        }
+    }
+
+    /// Returns a resampled copy of the image.
+    pub fn scale(&self, width: i32, height: i32) -> Result<Image, String> {
+        if width <= 0 || height <= 0 {
+            return Err(format!("Invalid image size {}x{}.", width, height));
+        }
+
+        //from_rgba() made sure the buffer matches the size
+        let source = image::RgbaImage::from_raw(self.width as u32, self.height as u32, self.pixels.clone()).unwrap();
+        let scaled = image::imageops::resize(&source, width as u32, height as u32,
+            image::imageops::FilterType::Triangle);
+
+        Image::from_rgba(width, height, scaled.into_raw())
+    }
+
+    pub fn width(&self) -> i32 {
+        return self.width;
+    }
+
+    pub fn height(&self) -> i32 {
+        return self.height;
+    }
+
+    pub fn get_bitmap(&self) -> winapi::HBITMAP {
+        return self.bitmap;
+    }
+}
+
+impl Drop for Image {
+    fn drop(&mut self) {
+        unsafe {
+            gdi32::DeleteObject(self.bitmap as winapi::HGDIOBJ);
+        }
+    }
+}
+
 pub trait WindowEventHandler {
 	fn on_command(&mut self, source_id: u16, command_type: u16) {
 		println!("Window got command from: {}.", source_id);
 	}
 
+	/// The size is that of the whole client area. Use
+	/// Window::get_client_area() for the part not covered by an attached
+	/// ToolBar or StatusBar.
 	fn on_size(&mut self, width: i32, height: i32) {
 		println!("Window resized. {} {}.", width, height);
 	}
@@ -736,6 +7116,60 @@ pub trait WindowEventHandler {
 		println!("Window destroyed.");
 	}
 
+    /// Called when part of the window needs to be redrawn. dirty is the
+    /// region that needs painting, in client coordinates.
+    fn on_paint(&mut self, graphics: &Graphics, dirty: &winapi::RECT) {
+    }
+
+    /// The text of an Edit changed, by the user or from code. Give the
+    /// Edit an id with Edit::set_id() to tell several apart.
+    fn on_text_change(&mut self, source_id: u16) {
+	}
+
+    /// A ListView column header was clicked.
+    fn on_column_click(&mut self, source_id: u16, column: i32) {
+	}
+
+    /// A ListView row was double clicked or Enter was pressed on it.
+    fn on_item_activate(&mut self, source_id: u16, row: i32) {
+	}
+
+    /// The selected rows of a ListView or the selected node of a TreeView
+    /// changed.
+    fn on_selection_change(&mut self, source_id: u16) {
+	}
+
+    /// A node added with TreeView::insert_lazy() is expanded for the first
+    /// time. Add its children here.
+    fn on_item_populate(&mut self, source_id: u16, item: TreeItem) {
+	}
+
+    /// The user selected another tab of a TabControl.
+    fn on_tab_change(&mut self, source_id: u16, index: i32) {
+	}
+
+    /// The value of a Slider or SpinBox was changed by the user.
+    fn on_value_change(&mut self, source_id: u16, value: i32) {
+	}
+
+    /// The window's own scroll bar was used. request is one of the SB_*
+    /// codes and position the current or dragged-to position. See
+    /// Window::apply_scroll_request() for the usual response.
+    fn on_scroll(&mut self, bar: ScrollBar, request: u16, position: i32) {
+	}
+
+    /// The mouse wheel turned. delta is a multiple of 120 per notch,
+    /// positive away from the user. x and y are in client coordinates.
+    /// Return true if the wheel was used; otherwise it is passed on to the
+    /// parent window, which lets a ScrollView scroll its content.
+    fn on_mouse_wheel(&mut self, delta: i32, x: i32, y: i32) -> bool {
+        false
+	}
+
+    /// A TreeView node was expanded or collapsed.
+    fn on_item_expand(&mut self, source_id: u16, item: TreeItem, expanded: bool) {
+	}
+
     fn on_timer(&mut self, window: winapi::HWND, id : usize) {
         println!("Timer fired.");
     }
@@ -752,8 +7186,155 @@ pub trait WindowEventHandler {
 				self.on_size(winapi::LOWORD(l_param as winapi::DWORD) as i32, winapi::HIWORD(l_param as winapi::DWORD) as i32);
 			},
 			winapi::WM_COMMAND => {
-				self.on_command(winapi::LOWORD(w_param as winapi::DWORD), winapi::HIWORD(w_param as winapi::DWORD));
+                let EN_CHANGE = 0x0300;
+                let source_id = winapi::LOWORD(w_param as winapi::DWORD);
+                let command_type = winapi::HIWORD(w_param as winapi::DWORD);
+
+				self.on_command(source_id, command_type);
+
+                //Only edit controls send this code
+                if command_type == EN_CHANGE && l_param != 0 {
+                    self.on_text_change(source_id);
+                }
 			},
+            winapi::WM_HSCROLL | winapi::WM_VSCROLL if l_param == 0 => {
+                let SB_THUMBPOSITION = 4;
+                let SB_THUMBTRACK = 5;
+                let SIF_POS = 0x0004;
+                let SIF_TRACKPOS = 0x0010;
+                let bar = if message == winapi::WM_HSCROLL {ScrollBar::Horizontal} else {ScrollBar::Vertical};
+                let request = winapi::LOWORD(w_param as winapi::DWORD);
+                let mut info = new_scroll_info(SIF_POS | SIF_TRACKPOS);
+
+                unsafe {
+                    user32::GetScrollInfo(window, bar as i32, &mut info);
+                }
+
+                //The 16 bit position in w_param is too small for long content
+                let position = if request == SB_THUMBPOSITION || request == SB_THUMBTRACK {info.nTrackPos} else {info.nPos};
+
+                self.on_scroll(bar, request, position);
+            },
+            winapi::WM_MOUSEWHEEL => {
+                let mut point = winapi::POINT {
+                    x: winapi::GET_X_LPARAM(l_param),
+                    y: winapi::GET_Y_LPARAM(l_param)
+                };
+
+                //Wheel messages carry screen coordinates
+                unsafe {
+                    user32::ScreenToClient(window, &mut point);
+                }
+
+                return self.on_mouse_wheel(winapi::HIWORD(w_param as winapi::DWORD) as i16 as i32, point.x, point.y);
+            },
+            winapi::WM_HSCROLL | winapi::WM_VSCROLL if l_param != 0 => {
+                let TBM_GETPOS = 0x0400;
+                let UDM_GETPOS32 = 0x0472;
+                let SB_ENDSCROLL = 8;
+                let control = l_param as winapi::HWND;
+
+                //Every change is followed by an end notification. Skip it.
+                if winapi::LOWORD(w_param as winapi::DWORD) as i32 == SB_ENDSCROLL {
+                    return true;
+                }
+
+                let UDM_GETBUDDY = 0x046A;
+                let class_name = get_class_name(control);
+                let (value, source) = unsafe {
+                    if class_name == "msctls_trackbar32" {
+                        (user32::SendMessageW(control, TBM_GETPOS, 0, 0) as i32, control)
+                    } else if class_name == "msctls_updown32" {
+                        let buddy = user32::SendMessageW(control, UDM_GETBUDDY, 0, 0) as winapi::HWND;
+
+                        //A SpinBox is known by the id of its edit box
+                        (user32::SendMessageW(control, UDM_GETPOS32, 0, 0) as i32, if buddy.is_null() {control} else {buddy})
+                    } else {
+                        return false;
+                    }
+                };
+                let source_id = unsafe {
+                    user32::GetDlgCtrlID(source) as u16
+                };
+
+                self.on_value_change(source_id, value);
+            },
+            winapi::WM_NOTIFY => {
+                let LVN_ITEMCHANGED = -101i32 as winapi::UINT;
+                let LVN_COLUMNCLICK = -108i32 as winapi::UINT;
+                let LVN_ITEMACTIVATE = -114i32 as winapi::UINT;
+                let LVIF_STATE = 0x0008;
+                let LVIS_SELECTED = 0x0002;
+                let TCN_SELCHANGE = -551i32 as winapi::UINT;
+                let TVN_SELCHANGEDW = -451i32 as winapi::UINT;
+                let TVN_ITEMEXPANDINGW = -454i32 as winapi::UINT;
+                let TVN_ITEMEXPANDEDW = -455i32 as winapi::UINT;
+                let TVM_GETNEXTITEM = 0x110A;
+                let TVM_SETITEMW = 0x113F;
+                let TVGN_CHILD = 4;
+                let TVIF_CHILDREN = 0x0040;
+                let TVE_EXPAND = 0x0002;
+                let TVIS_EXPANDEDONCE = 0x0040;
+
+                let header = unsafe { &*(l_param as *const winapi::NMHDR) };
+                let source_id = header.idFrom as u16;
+
+                if header.code == LVN_COLUMNCLICK {
+                    let info = unsafe { &*(l_param as *const winapi::NMLISTVIEW) };
+
+                    self.on_column_click(source_id, info.iSubItem);
+                } else if header.code == LVN_ITEMACTIVATE {
+                    let info = unsafe { &*(l_param as *const winapi::NMLISTVIEW) };
+
+                    self.on_item_activate(source_id, info.iItem);
+                } else if header.code == TCN_SELCHANGE {
+                    let TCM_GETCURSEL = 0x130B;
+                    let index = unsafe {
+                        user32::SendMessageW(header.hwndFrom, TCM_GETCURSEL, 0, 0) as i32
+                    };
+
+                    self.on_tab_change(source_id, index);
+                } else if header.code == TVN_SELCHANGEDW {
+                    self.on_selection_change(source_id);
+                } else if header.code == TVN_ITEMEXPANDINGW {
+                    let info = unsafe { &*(l_param as *const winapi::NMTREEVIEWW) };
+                    let item = TreeItem {
+                        item: info.itemNew.hItem
+                    };
+
+                    if info.action & TVE_EXPAND != 0 && info.itemNew.state & TVIS_EXPANDEDONCE == 0 {
+                        self.on_item_populate(source_id, item);
+
+                        unsafe {
+                            //Drop the expand button if nothing was added
+                            if user32::SendMessageW(header.hwndFrom, TVM_GETNEXTITEM, TVGN_CHILD, item.item as winapi::LPARAM) == 0 {
+                                let mut update: winapi::TVITEMW = mem::zeroed();
+
+                                update.mask = TVIF_CHILDREN;
+                                update.hItem = item.item;
+                                update.cChildren = 0;
+
+                                user32::SendMessageW(header.hwndFrom, TVM_SETITEMW, 0, &update as *const winapi::TVITEMW as winapi::LPARAM);
+                            }
+                        }
+                    }
+                } else if header.code == TVN_ITEMEXPANDEDW {
+                    let info = unsafe { &*(l_param as *const winapi::NMTREEVIEWW) };
+
+                    self.on_item_expand(source_id, TreeItem {
+                        item: info.itemNew.hItem
+                    }, info.action & TVE_EXPAND != 0);
+                } else if header.code == LVN_ITEMCHANGED {
+                    let info = unsafe { &*(l_param as *const winapi::NMLISTVIEW) };
+
+                    if info.uChanged & LVIF_STATE != 0 &&
+                        (info.uNewState ^ info.uOldState) & LVIS_SELECTED != 0 {
+                        self.on_selection_change(source_id);
+                    }
+                } else {
+                    return false;
+                }
+            },
             winapi::WM_MOUSEMOVE => {
 				self.on_mouse_move(winapi::GET_X_LPARAM(l_param), winapi::GET_Y_LPARAM(l_param));
 			},
@@ -778,6 +7359,79 @@ pub trait WindowEventHandler {
             winapi::WM_DESTROY => {
 				self.on_destroy();
 			},
+            winapi::WM_PAINT => {
+                let mut ps = winapi::PAINTSTRUCT {
+                    hdc: 0 as winapi::HDC,
+                    fErase: 0,
+                    rcPaint: winapi::RECT {
+                        top: 0,
+                        left: 0,
+                        right: 0,
+                        bottom: 0
+                    },
+                    fRestore: 0,
+                    fIncUpdate: 0,
+                    rgbReserved: [0; 32]
+                };
+
+                unsafe {
+                    user32::BeginPaint(window, &mut ps);
+
+                    if is_double_buffered(window) {
+                        let mut client = winapi::RECT {
+                            top: 0, left: 0, right: 0, bottom: 0
+                        };
+
+                        user32::GetClientRect(window, &mut client);
+
+                        if client.right > 0 && client.bottom > 0 {
+                            let memory_dc = gdi32::CreateCompatibleDC(ps.hdc);
+                            let bitmap = gdi32::CreateCompatibleBitmap(ps.hdc, client.right, client.bottom);
+                            let old_bitmap = gdi32::SelectObject(memory_dc, bitmap as winapi::HGDIOBJ);
+
+                            //Background erase was skipped. Do it off-screen instead.
+                            erase_background(window, memory_dc);
+
+                            if let Some(color) = get_color_prop(window, "cwnd.fgcolor") {
+                                gdi32::SetTextColor(memory_dc, color);
+                            }
+
+                            let graphics = Graphics {
+                                dc: memory_dc
+                            };
+
+                            self.on_paint(&graphics, &ps.rcPaint);
+
+                            gdi32::BitBlt(ps.hdc,
+                                ps.rcPaint.left, ps.rcPaint.top,
+                                ps.rcPaint.right - ps.rcPaint.left, ps.rcPaint.bottom - ps.rcPaint.top,
+                                memory_dc, ps.rcPaint.left, ps.rcPaint.top, winapi::SRCCOPY);
+
+                            gdi32::SelectObject(memory_dc, old_bitmap);
+                            gdi32::DeleteObject(bitmap as winapi::HGDIOBJ);
+                            gdi32::DeleteDC(memory_dc);
+                        }
+                    } else {
+                        if let Some(color) = get_color_prop(window, "cwnd.fgcolor") {
+                            gdi32::SetTextColor(ps.hdc, color);
+                        }
+
+                        let graphics = Graphics {
+                            dc: ps.hdc
+                        };
+
+                        self.on_paint(&graphics, &ps.rcPaint);
+                    }
+
+                    user32::EndPaint(window, &ps);
+                }
+            },
+            winapi::WM_ERASEBKGND => {
+                //Double buffered windows erase into the off-screen bitmap
+                if !is_double_buffered(window) {
+                    return false;
+                }
+            },
 			_ => {
 				return false;
 			}
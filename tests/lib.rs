@@ -1,5 +1,6 @@
 extern crate howl;
 extern crate winapi;
+extern crate user32;
 
 use howl::*;
 
@@ -92,3 +93,1038 @@ fn size_test() {
     assert_eq!(100, width);
     assert_eq!(100, height);
 }
+
+#[test]
+fn ui_handle_test() {
+    Application::init();
+
+    let handle = Application::ui_handle();
+    assert_eq!(Some(5), handle.send(|| 2 + 3));
+
+    let worker = std::thread::spawn(move || {
+        assert_eq!(false, handle.is_ui_thread());
+
+        let result = handle.send(|| 40 + 2);
+
+        handle.post(|| Application::exit_loop());
+
+        result
+    });
+
+    Application::main_loop();
+
+    assert_eq!(Some(42), worker.join().unwrap());
+}
+
+#[test]
+fn spawn_local_test() {
+    use std::cell::Cell;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    //Sets steps to 1, sleeps, then sets it to 2 and ends the loop
+    struct Steps {
+        steps: Rc<Cell<i32>>,
+        sleep: Option<Sleep>
+    }
+
+    impl Future for Steps {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, context: &mut Context) -> Poll<()> {
+            let this = self.get_mut();
+
+            if this.sleep.is_none() {
+                this.steps.set(1);
+                this.sleep = Some(sleep(Duration::from_millis(10)));
+            }
+
+            match Pin::new(this.sleep.as_mut().unwrap()).poll(context) {
+                Poll::Ready(()) => {
+                    this.steps.set(2);
+                    Application::exit_loop();
+
+                    Poll::Ready(())
+                },
+                Poll::Pending => Poll::Pending
+            }
+        }
+    }
+
+    Application::init();
+
+    let steps = Rc::new(Cell::new(0));
+
+    spawn_local(Steps {
+        steps: steps.clone(),
+        sleep: None
+    });
+
+    assert_eq!(0, steps.get());
+
+    Application::main_loop();
+
+    assert_eq!(2, steps.get());
+}
+
+#[test]
+fn timer_test() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    Application::init();
+    VirtualClock::install();
+
+    let wnd = Frame::new("My Main Window", 200, 400);
+    let ticks = Rc::new(Cell::new(0));
+    let fired = Rc::new(Cell::new(false));
+
+    let tick_count = ticks.clone();
+    let repeating = wnd.every(Duration::from_millis(100), move || tick_count.set(tick_count.get() + 1));
+    let fired_flag = fired.clone();
+    let _one_shot = wnd.after(Duration::from_millis(250), move || fired_flag.set(true));
+
+    VirtualClock::advance(Duration::from_millis(200));
+    assert_eq!(2, ticks.get());
+    assert_eq!(false, fired.get());
+
+    VirtualClock::advance(Duration::from_millis(100));
+    assert_eq!(3, ticks.get());
+    assert_eq!(true, fired.get());
+
+    drop(repeating);
+    VirtualClock::advance(Duration::from_millis(1000));
+    assert_eq!(3, ticks.get());
+
+    VirtualClock::uninstall();
+}
+
+#[test]
+fn idle_test() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    Application::init();
+
+    let calls = Rc::new(Cell::new(0));
+    let idle_calls = calls.clone();
+
+    Application::on_idle(move || {
+        idle_calls.set(idle_calls.get() + 1);
+
+        if idle_calls.get() == 3 {
+            Application::exit_loop();
+        }
+
+        true
+    });
+
+    Application::main_loop();
+    Application::clear_idle();
+
+    assert_eq!(3, calls.get());
+}
+
+#[test]
+fn double_buffered_test() {
+    Application::init();
+
+    let wnd = Frame::new("My Main Window", 200, 400);
+    let canvas = Canvas::new(&wnd, 0, 0, 100, 100);
+
+    assert_eq!(false, canvas.is_double_buffered());
+
+    canvas.set_double_buffered(true);
+    assert_eq!(true, canvas.is_double_buffered());
+
+    canvas.set_double_buffered(false);
+    assert_eq!(false, canvas.is_double_buffered());
+}
+
+#[test]
+fn image_test() {
+    let pixels = vec![255u8; 4 * 4 * 4];
+    let img = Image::from_rgba(4, 4, pixels).unwrap();
+
+    assert_eq!(4, img.width());
+    assert_eq!(4, img.height());
+
+    let scaled = img.scale(8, 2).unwrap();
+    assert_eq!(8, scaled.width());
+    assert_eq!(2, scaled.height());
+
+    assert!(Image::from_rgba(4, 4, vec![0; 10]).is_err());
+    assert!(Image::from_rgba(0x10000, 0x10000, Vec::new()).is_err());
+    assert!(img.scale(0, 2).is_err());
+
+    assert!(Image::from_bytes(&[1, 2, 3]).is_err());
+    assert!(Image::from_file("does-not-exist.png").is_err());
+}
+
+#[test]
+fn font_test() {
+    Application::init();
+
+    let wnd = Frame::new("My Main Window", 200, 400);
+    let edt = Edit::new(&wnd, 0, 0, 100, 100, true);
+
+    let font = FontBuilder::new()
+        .family("Arial")
+        .size(12)
+        .bold()
+        .italic(true)
+        .create();
+
+    assert!(!font.get_handle().is_null());
+
+    edt.set_font(&font);
+    edt.set_text("Styled");
+    assert_eq!("Styled", edt.get_text());
+}
+
+#[test]
+fn color_test() {
+    assert_eq!(Some(Color::rgb(255, 0, 0)), Color::from_hex("#ff0000"));
+    assert_eq!(Some(Color::rgb(0x11, 0x22, 0x33)), Color::from_hex("123"));
+    assert_eq!(Some(Color::rgba(1, 2, 3, 4)), Color::from_hex("#01020304"));
+    assert_eq!(None, Color::from_hex("#12345"));
+    assert_eq!(None, Color::from_hex("#gg0000"));
+    assert_eq!(Some(Color::BLUE), Color::from_name("Blue"));
+    assert_eq!(None, Color::from_name("no such color"));
+    assert_eq!(0x00332211, Color::rgb(0x11, 0x22, 0x33).to_colorref());
+
+    Application::init();
+
+    let wnd = Frame::new("My Main Window", 200, 400);
+    let edt = Edit::new(&wnd, 0, 0, 100, 100, true);
+
+    assert_eq!(None, edt.get_background_color());
+
+    wnd.set_background_color(Color::WHITE);
+    edt.set_background_color(Color::BLACK);
+    edt.set_text_color(Color::rgb(0, 255, 0));
+
+    assert_eq!(Some(Color::WHITE), wnd.get_background_color());
+    assert_eq!(Some(Color::BLACK), edt.get_background_color());
+    assert_eq!(Some(Color::rgb(0, 255, 0)), edt.get_text_color());
+}
+
+#[test]
+fn list_box_items_test() {
+    Application::init();
+
+    let wnd = Frame::new("My Main Window", 200, 400);
+
+    let lb = ListBox::new(&wnd, 0, 0, 0, 100, 100);
+    lb.add_item("Banana");
+    lb.add_item("Cherry");
+    assert_eq!(0, lb.insert_item(0, "Apple"));
+    assert_eq!(3, lb.insert_item(10, "Date"));
+
+    assert_eq!("Apple", lb.get_item_text(0));
+    assert_eq!("Date", lb.get_item_text(3));
+    assert_eq!(2, lb.find_item("ch"));
+    assert_eq!(-1, lb.find_item("Fig"));
+
+    lb.set_sel(1);
+    lb.set_item_text(1, "Blueberry");
+    assert_eq!("Blueberry", lb.get_item_text(1));
+    assert_eq!(vec![1], lb.get_selected_indices());
+
+    assert_eq!(-1, lb.set_item_text(10, "Fig"));
+    assert_eq!(-1, lb.set_item_text(u32::MAX, "Fig"));
+    assert_eq!(4, lb.get_item_count());
+
+    let mut options = ListBoxOptions::new();
+    options.selection = SelectionMode::Multiple;
+    options.sorted = true;
+
+    let multi = ListBox::with_options(&wnd, 1, 0, 0, 100, 100, &options);
+    multi.add_item("Cherry");
+    multi.add_item("Apple");
+    multi.insert_item(0, "Banana");
+    assert_eq!("Apple", multi.get_item_text(0));
+    assert_eq!("Banana", multi.get_item_text(1));
+
+    multi.set_selected(0, true);
+    multi.set_selected(2, true);
+    assert_eq!(vec![0, 2], multi.get_selected_indices());
+
+    multi.set_selected(0, false);
+    assert_eq!(false, multi.is_selected(0));
+    assert_eq!(vec![2], multi.get_selected_indices());
+}
+
+#[test]
+fn typed_list_box_test() {
+    Application::init();
+
+    let wnd = Frame::new("My Main Window", 200, 400);
+
+    let mut options = ListBoxOptions::new();
+    options.sorted = true;
+
+    let mut lb = TypedListBox::with_options(&wnd, 0, 0, 0, 100, 100, &options);
+    lb.add_item("Cherry", 3);
+    lb.add_item("Apple", 1);
+    lb.add_item("Banana", 2);
+
+    assert_eq!(Some(&1), lb.get_data(0));
+    assert_eq!(Some(&2), lb.get_data(1));
+    assert_eq!(Some(&3), lb.get_data(2));
+
+    assert_eq!(Some(1), lb.delete_item(0));
+    assert_eq!(Some(&2), lb.get_data(0));
+    assert_eq!("Banana", lb.get_item_text(0));
+
+    *lb.get_data_mut(1).unwrap() = 30;
+    lb.set_sel(1);
+    assert_eq!(Some(&30), lb.get_selected_data());
+
+    assert_eq!(None, lb.get_data(5));
+
+    lb.clear();
+    assert_eq!(0, lb.get_item_count());
+
+    //Values are dropped with the window, before the TypedListBox
+    let value = std::rc::Rc::new(5);
+    let mut shared = TypedListBox::new(&wnd, 1, 0, 0, 100, 100);
+    shared.add_item("Date", value.clone());
+    assert_eq!(2, std::rc::Rc::strong_count(&value));
+
+    unsafe {
+        user32::DestroyWindow(wnd.get_hwnd());
+    }
+
+    assert_eq!(1, std::rc::Rc::strong_count(&value));
+}
+
+#[test]
+fn virtual_list_test() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    struct Lines {
+        count: Cell<usize>
+    }
+
+    impl ListModel for Lines {
+        fn len(&self) -> usize {
+            self.count.get()
+        }
+        fn item(&self, index: usize) -> String {
+            format!("Line {}", index)
+        }
+    }
+
+    Application::init();
+
+    let wnd = Frame::new("My Main Window", 200, 400);
+    let model = Rc::new(Lines {
+        count: Cell::new(1000000)
+    });
+
+    let list = VirtualList::new(&wnd, 0, 0, 0, 100, 100, model.clone());
+    assert_eq!(1000000, list.get_item_count());
+
+    list.set_sel(500000);
+    list.ensure_visible(500000);
+    assert_eq!(500000, list.get_sel());
+
+    model.count.set(2000000);
+    list.notifier().changed();
+    assert_eq!(2000000, list.get_item_count());
+    assert_eq!(500000, list.get_sel());
+
+    model.count.set(10);
+    list.refresh();
+    assert_eq!(10, list.get_item_count());
+
+    //Out of range and reversed bounds must not panic
+    list.refresh_rows(5, usize::MAX);
+    list.refresh_rows(9, 2);
+}
+
+#[test]
+fn list_view_test() {
+    Application::init();
+
+    let wnd = Frame::new("My Main Window", 200, 400);
+
+    let lv = ListView::new(&wnd, 0, 0, 0, 200, 200);
+    assert_eq!(0, lv.add_column("Name", 100));
+    assert_eq!(1, lv.add_column("Size", 50));
+    assert_eq!(2, lv.get_column_count());
+
+    lv.add_row(&["b.txt", "20"]);
+    lv.add_row(&["c.txt", "5"]);
+    assert_eq!(0, lv.insert_row(0, &["a.txt", "100"]));
+    assert_eq!(3, lv.get_row_count());
+
+    assert_eq!("a.txt", lv.get_cell_text(0, 0));
+    assert_eq!("100", lv.get_cell_text(0, 1));
+
+    lv.set_cell_text(1, 1, "21");
+    assert_eq!("21", lv.get_cell_text(1, 1));
+
+    lv.sort_by(|a, b| {
+        let a: i32 = a[1].parse().unwrap();
+        let b: i32 = b[1].parse().unwrap();
+
+        a.cmp(&b)
+    });
+    lv.set_sort_indicator(1, Some(true));
+    assert_eq!("c.txt", lv.get_cell_text(0, 0));
+    assert_eq!("a.txt", lv.get_cell_text(2, 0));
+
+    lv.set_selected(0, true);
+    lv.set_selected(2, true);
+    assert_eq!(vec![0, 2], lv.get_selected_rows());
+
+    lv.delete_row(0);
+    assert_eq!(2, lv.get_row_count());
+
+    lv.clear();
+    assert_eq!(0, lv.get_row_count());
+}
+
+#[test]
+fn tree_view_test() {
+    Application::init();
+
+    let wnd = Frame::new("My Main Window", 200, 400);
+
+    let mut tv = TreeView::new(&wnd, 0, 0, 0, 200, 200, true);
+    let root = tv.insert(None, "C:", String::from("C:\\")).unwrap();
+    let windows = tv.insert(Some(root), "Windows", String::from("C:\\Windows")).unwrap();
+    let users = tv.insert_lazy(Some(root), "Users", String::from("C:\\Users")).unwrap();
+    tv.insert(Some(windows), "System32", String::from("C:\\Windows\\System32")).unwrap();
+
+    assert_eq!(4, tv.get_count());
+    assert_eq!(vec![root], tv.get_children(None));
+    assert_eq!(vec![windows, users], tv.get_children(Some(root)));
+    assert_eq!(Some(root), tv.get_parent(users));
+    assert_eq!("Users", tv.get_text(users));
+    assert_eq!("C:\\Users", tv.get_data(users).unwrap());
+
+    tv.set_text(users, "Home");
+    assert_eq!("Home", tv.get_text(users));
+
+    tv.get_data_mut(users).unwrap().push_str("\\Public");
+    assert_eq!("C:\\Users\\Public", tv.get_data(users).unwrap());
+
+    tv.expand(root);
+    assert!(tv.is_expanded(root));
+
+    tv.select(windows);
+    assert_eq!(Some(windows), tv.get_selection());
+
+    tv.set_checked(windows, true);
+    assert!(tv.is_checked(windows));
+    assert!(!tv.is_checked(users));
+
+    tv.remove(windows);
+    assert_eq!(2, tv.get_count());
+
+    tv.clear();
+    assert_eq!(0, tv.get_count());
+
+    //Values are dropped with the window, before the TreeView
+    let value = std::rc::Rc::new(5);
+    let mut shared = TreeView::new(&wnd, 1, 0, 0, 200, 200, false);
+    let parent = shared.insert(None, "Parent", value.clone()).unwrap();
+    shared.insert(Some(parent), "Child", value.clone()).unwrap();
+    assert_eq!(3, std::rc::Rc::strong_count(&value));
+
+    unsafe {
+        user32::DestroyWindow(wnd.get_hwnd());
+    }
+
+    assert_eq!(1, std::rc::Rc::strong_count(&value));
+}
+
+#[test]
+fn tab_control_test() {
+    Application::init();
+
+    let wnd = Frame::new("My Main Window", 200, 400);
+
+    let tabs = TabControl::new(&wnd, 0, 0, 0, 200, 200);
+    assert_eq!(-1, tabs.get_selected());
+
+    let general = tabs.add_tab("General");
+    let advanced = tabs.add_tab("Advanced");
+    let cb = Checkbox::new(&advanced, 10, "Verbose", 10, 10, 100, 20);
+
+    assert_eq!(2, tabs.get_tab_count());
+    assert_eq!(0, tabs.get_selected());
+    assert_eq!("Verbose", cb.get_text());
+
+    tabs.rename_tab(1, "Expert");
+    assert_eq!("Expert", tabs.get_tab_title(1));
+
+    tabs.select(1);
+    assert_eq!(1, tabs.get_selected());
+    assert_eq!(advanced.get_hwnd(), tabs.get_page(1).unwrap().get_hwnd());
+
+    tabs.remove_tab(1);
+    assert_eq!(1, tabs.get_tab_count());
+    assert_eq!(0, tabs.get_selected());
+    assert_eq!(general.get_hwnd(), tabs.get_page(0).unwrap().get_hwnd());
+}
+
+#[test]
+fn range_controls_test() {
+    Application::init();
+
+    let wnd = Frame::new("My Main Window", 200, 400);
+
+    let progress = ProgressBar::new(&wnd, 0, 0, 100, 20);
+    progress.set_range(0, 10);
+    progress.set_value(4);
+    assert_eq!(4, progress.get_value());
+
+    progress.set_marquee(true, 30);
+    assert!(progress.is_marquee());
+    progress.set_marquee(false, 0);
+    assert!(!progress.is_marquee());
+
+    let slider = Slider::new(&wnd, 1, 0, 30, 100, 30, false);
+    slider.set_range(-50, 50);
+    slider.set_tick_frequency(10);
+    assert_eq!((-50, 50), slider.get_range());
+
+    slider.set_value(25);
+    assert_eq!(25, slider.get_value());
+    slider.set_value(80);
+    assert_eq!(50, slider.get_value());
+
+    let spin = SpinBox::new(&wnd, 2, 0, 70, 80, 20);
+    spin.set_range(1, 10);
+    spin.set_step(2);
+    spin.set_value(7);
+    assert_eq!(7, spin.get_value());
+    assert_eq!("7", spin.get_text());
+
+    spin.set_text("9");
+    assert_eq!(9, spin.get_value());
+}
+
+#[test]
+fn bars_test() {
+    Application::init();
+
+    let wnd = Frame::new("My Main Window", 300, 400);
+    let full = wnd.get_client_area();
+
+    let status = StatusBar::new(&wnd);
+    status.set_parts(&[100, -1]);
+    status.set_part_text(0, "Ready");
+    status.set_part_text(1, "Line 1");
+    assert_eq!("Ready", status.get_part_text(0));
+    assert_eq!("Line 1", status.get_part_text(1));
+
+    let toolbar = ToolBar::new(&wnd);
+    toolbar.set_icons(&[&Image::from_rgba(16, 16, vec![0; 16 * 16 * 4]).unwrap()]);
+    toolbar.add_button(100, 0, "Open");
+    toolbar.add_separator();
+    toolbar.add_toggle_button(101, 0, "Word wrap");
+    assert_eq!(3, toolbar.get_button_count());
+
+    toolbar.set_checked(101, true);
+    assert!(toolbar.is_checked(101));
+    toolbar.set_checked(101, false);
+    assert!(!toolbar.is_checked(101));
+
+    wnd.show();
+
+    let area = wnd.get_client_area();
+    assert!(area.top > full.top);
+    assert!(area.bottom < full.bottom);
+}
+
+#[test]
+fn tooltip_test() {
+    Application::init();
+
+    let wnd = Frame::new("My Main Window", 200, 400);
+    let btn = Button::new(&wnd, 1, "Save", 10, 10, 80, 20);
+    let canvas = Canvas::new(&wnd, 10, 40, 100, 100);
+
+    assert_eq!(None, btn.get_tooltip());
+
+    btn.set_tooltip("Save the file");
+    assert_eq!(Some(String::from("Save the file")), btn.get_tooltip());
+
+    btn.set_tooltip("Save the document");
+    assert_eq!(Some(String::from("Save the document")), btn.get_tooltip());
+
+    canvas.set_tooltip_callback(|x, y| if x < 50 {Some(format!("{}, {}", x, y))} else {None});
+    wnd.set_tooltip_delay(100);
+
+    btn.remove_tooltip();
+    assert_eq!(None, btn.get_tooltip());
+}
+
+#[test]
+fn splitter_test() {
+    Application::init();
+
+    let mut width = 0i32;
+    let mut height = 0i32;
+
+    let wnd = Frame::new("My Main Window", 400, 400);
+    let splitter = Splitter::new(&wnd, 0, 0, 305, 200, Orientation::Horizontal);
+    let lb = ListBox::new(&splitter, 1, 0, 0, 10, 10);
+    let nested = Splitter::new(&splitter, 0, 0, 10, 10, Orientation::Vertical);
+    let edt = Edit::new(&nested, 0, 0, 10, 10, true);
+    let edt2 = Edit::new(&nested, 0, 0, 10, 10, true);
+
+    splitter.set_panes(&lb, &nested);
+    nested.set_panes(&edt, &edt2);
+
+    splitter.set_ratio(0.4);
+    assert_eq!(0.4, splitter.get_ratio());
+
+    lb.size(&mut width, &mut height);
+    assert_eq!(120, width);
+
+    nested.size(&mut width, &mut height);
+    assert_eq!(180, width);
+
+    splitter.set_min_sizes(10, 250);
+    lb.size(&mut width, &mut height);
+    assert_eq!(50, width);
+}
+
+#[test]
+fn scroll_view_test() {
+    Application::init();
+
+    let wnd = Frame::new("My Main Window", 400, 400);
+    let view = ScrollView::new(&wnd, 0, 0, 200, 200);
+    let edt = Edit::new(view.get_content(), 10, 900, 100, 20, false);
+    edt.set_text("Bottom");
+
+    view.set_virtual_size(1000, 1000);
+    assert_eq!((0, 0), view.get_scroll_offset());
+
+    view.scroll_to(100, 500);
+    assert_eq!((100, 500), view.get_scroll_offset());
+
+    let position = view.get_content().get_position();
+    assert_eq!(-100, position.left);
+    assert_eq!(-500, position.top);
+
+    view.scroll_to(5000, 5000);
+    let (x, y) = view.get_scroll_offset();
+    assert!(x > 700 && x < 1000);
+    assert!(y > 700 && y < 1000);
+
+    //The wheel over a child with a default handler scrolls the view
+    struct Plain;
+    impl WindowEventHandler for Plain {}
+
+    let handler = Plain;
+    let child = Canvas::new(view.get_content(), 0, 0, 1000, 1000);
+    child.attach_event_handler(&handler);
+    view.scroll_to(0, 0);
+
+    unsafe {
+        let WHEEL_DOWN = (-120i16 as u16 as winapi::WPARAM) << 16;
+
+        user32::SendMessageW(child.get_hwnd(), winapi::WM_MOUSEWHEEL, WHEEL_DOWN, 0);
+    }
+
+    assert!(view.get_scroll_offset().1 > 0);
+
+    let mut child_window = child.get_hwnd();
+    child_window.detach_event_handler();
+
+    let canvas = Canvas::new(&wnd, 200, 0, 100, 100);
+    canvas.set_scroll_range(ScrollBar::Vertical, 500, 100);
+    assert_eq!(150, canvas.set_scroll_position(ScrollBar::Vertical, 150));
+    assert_eq!(400, canvas.set_scroll_position(ScrollBar::Vertical, 1000));
+    assert_eq!(0, canvas.set_scroll_position(ScrollBar::Vertical, -10));
+}
+
+#[test]
+fn edit_text_test() {
+    Application::init();
+
+    let wnd = Frame::new("My Main Window", 200, 400);
+    let edt = Edit::new(&wnd, 0, 0, 100, 100, true);
+
+    edt.set_id(42);
+    assert_eq!(42, edt.get_id());
+
+    edt.set_text("Hello World\r\nSecond line");
+    assert_eq!(2, edt.line_count());
+    assert_eq!("Hello World", edt.get_line(0));
+    assert_eq!("Second line", edt.get_line(1));
+    assert_eq!("", edt.get_line(2));
+
+    edt.set_selection(6, 11);
+    assert_eq!((6, 11), edt.get_selection());
+    assert_eq!(11, edt.caret_position());
+
+    edt.replace_selection("Moon");
+    assert_eq!("Hello Moon", edt.get_line(0));
+    assert_eq!(10, edt.caret_position());
+    assert!(edt.can_undo());
+
+    edt.undo();
+    assert_eq!("Hello World", edt.get_line(0));
+
+    edt.set_max_length(5);
+    edt.scroll_to_caret();
+
+    //Lines too long for the EM_GETLINE size word
+    let mut options = EditOptions::new();
+    options.multi_line = true;
+    options.word_wrap = false;
+
+    let unwrapped = Edit::with_options(&wnd, 1, 0, 0, 100, 100, &options);
+    let long_line = "x".repeat(70000);
+    unwrapped.set_text(&format!("{}\r\nEnd", long_line));
+    assert_eq!(long_line, unwrapped.get_line(0));
+    assert_eq!("End", unwrapped.get_line(1));
+}
+
+fn get_style(window: &Window) -> winapi::DWORD {
+    unsafe {
+        user32::GetWindowLongW(window.get_hwnd(), winapi::GWL_STYLE) as winapi::DWORD
+    }
+}
+
+#[test]
+fn edit_options_test() {
+    let ES_RIGHT = 0x0002;
+    let ES_MULTILINE = 0x0004;
+    let ES_UPPERCASE = 0x0008;
+    let ES_LOWERCASE = 0x0010;
+    let ES_PASSWORD = 0x0020;
+    let ES_AUTOHSCROLL = 0x0080;
+    let ES_NOHIDESEL = 0x0100;
+    let ES_READONLY = 0x0800;
+    let ES_NUMBER = 0x2000;
+
+    Application::init();
+
+    let wnd = Frame::new("My Main Window", 200, 400);
+
+    let mut options = EditOptions::new();
+    options.password = true;
+    options.align = TextAlign::Right;
+    options.case = TextCase::Upper;
+    options.horizontal_scroll = true;
+    options.placeholder = Some(String::from("Password"));
+
+    let edt = Edit::with_options(&wnd, 7, 0, 0, 100, 20, &options);
+    assert_eq!(7, edt.get_id());
+
+    let style = get_style(&edt);
+    assert!(style & ES_PASSWORD != 0);
+    assert!(style & ES_RIGHT != 0);
+    assert!(style & ES_UPPERCASE != 0);
+    assert!(style & ES_AUTOHSCROLL != 0);
+    assert_eq!(0, style & (ES_MULTILINE | ES_LOWERCASE | ES_NUMBER | ES_READONLY));
+
+    //Cue banners need version 6 of the common controls, which only an
+    //application manifest turns on
+    let placeholder = edt.get_placeholder();
+    assert!(placeholder == "Password" || placeholder.is_empty());
+
+    edt.set_text("Secret");
+    assert_eq!("Secret", edt.get_text());
+
+    let mut options = EditOptions::new();
+    options.multi_line = true;
+    options.word_wrap = false;
+    options.read_only = true;
+    options.number_only = true;
+    options.keep_selection = true;
+    options.case = TextCase::Lower;
+
+    let edt = Edit::with_options(&wnd, 8, 0, 20, 100, 100, &options);
+
+    let style = get_style(&edt);
+    assert!(style & ES_MULTILINE != 0);
+    assert!(style & ES_AUTOHSCROLL != 0);
+    assert!(style & ES_READONLY != 0);
+    assert!(style & ES_NUMBER != 0);
+    assert!(style & ES_NOHIDESEL != 0);
+    assert!(style & ES_LOWERCASE != 0);
+    assert_eq!(0, style & (ES_PASSWORD | ES_RIGHT | ES_UPPERCASE));
+
+    edt.set_text("123\r\n456");
+    assert_eq!(2, edt.line_count());
+
+    //Multi-line edits wrap words unless told otherwise
+    let edt = Edit::new(&wnd, 0, 120, 100, 100, true);
+    assert_eq!(0, get_style(&edt) & ES_AUTOHSCROLL);
+}
+
+#[test]
+fn validator_test() {
+    let number = Validator::integer_range(1, 100);
+    assert!(number.is_valid("42"));
+    assert!(!number.is_valid("101"));
+    assert!(!number.is_valid("4x"));
+
+    let decimal = Validator::decimal();
+    assert!(decimal.is_valid("-12.5"));
+    assert!(decimal.is_valid(".5"));
+    assert!(!decimal.is_valid("."));
+    assert!(!decimal.is_valid("1.2.3"));
+
+    let date = Validator::date();
+    assert!(date.is_valid("2024-02-29"));
+    assert!(!date.is_valid("2023-02-29"));
+    assert!(!date.is_valid("2024-13-01"));
+    assert!(!date.is_valid("24-01-01"));
+
+    assert!(Validator::ip_address().is_valid("192.168.0.1"));
+    assert!(!Validator::ip_address().is_valid("192.168.0.256"));
+
+    let code = Validator::regex("[A-Z]{3}").unwrap();
+    assert!(code.is_valid("ABC"));
+    assert!(!code.is_valid("ABCD"));
+    assert!(Validator::regex("(").is_err());
+
+    //Reject mode lets through text that can still become valid
+    assert!(Validator::integer_range(10, 99).is_valid_prefix("1"));
+    assert!(!Validator::integer_range(10, 99).is_valid_prefix("123"));
+    assert!(Validator::integer_range(-5, 5).is_valid_prefix("-"));
+    assert!(!number.is_valid_prefix("-"));
+    assert!(decimal.is_valid_prefix("-12."));
+    assert!(!decimal.is_valid_prefix("1.2."));
+    assert!(date.is_valid_prefix("2024-0"));
+    assert!(!date.is_valid_prefix("2024-2"));
+    assert!(!date.is_valid_prefix("2023-02-29"));
+    assert!(Validator::ip_address().is_valid_prefix("192."));
+    assert!(!Validator::ip_address().is_valid_prefix("192..1"));
+    assert!(!Validator::ip_address().is_valid_prefix("300"));
+    assert!(!Validator::ip_address().is_valid("192.168.01.1"));
+    assert!(!Validator::ip_address().is_valid_prefix("192.168.01"));
+    assert!(Validator::ip_address().is_valid_prefix("192.168.0"));
+    assert!(code.is_valid_prefix("AB"));
+    assert!(!code.is_valid_prefix("A1"));
+    assert!(Validator::new(|text| text == "yes").is_valid_prefix("yes"));
+
+    let mask = InputMask::new("000-000-0000");
+    assert_eq!("___-___-____", mask.empty_text());
+    assert_eq!(Some((String::from("5__-___-____"), 1)), mask.insert("___-___-____", 0, '5'));
+    assert_eq!(None, mask.insert("___-___-____", 0, 'x'));
+    assert_eq!(Some((String::from("555-1__-____"), 5)), mask.insert("555-___-____", 3, '1'));
+    assert_eq!((String::from("555-123-4567"), 12), mask.insert_str("", 0, "555-123-4567"));
+    assert_eq!((String::from("55_-___-____"), 2), mask.erase("555-___-____", 4));
+    assert_eq!("555-_23-____", mask.delete("555-123-____", 3));
+    assert!(mask.is_complete("555-123-4567"));
+    assert!(!mask.is_complete("555-123-456_"));
+}
+
+#[test]
+fn edit_validation_test() {
+    Application::init();
+
+    let wnd = Frame::new("My Main Window", 200, 400);
+    let edt = Edit::new(&wnd, 0, 0, 100, 20, false);
+    assert!(edt.is_valid());
+
+    edt.set_validator(Validator::integer_range(1, 10), ValidationMode::Flag);
+    edt.set_text("5");
+    assert!(edt.is_valid());
+    edt.set_text("50");
+    assert!(!edt.is_valid());
+
+    edt.remove_validation();
+    assert!(edt.is_valid());
+
+    edt.set_input_mask(InputMask::new("000-000-0000"));
+    assert_eq!("___-___-____", edt.get_text());
+    assert!(!edt.is_valid());
+
+    edt.set_text("555-123-4567");
+    assert!(edt.is_valid());
+    edt.remove_validation();
+}
+
+fn type_chars(edit: &Edit, text: &str) {
+    for c in text.chars() {
+        unsafe {
+            user32::SendMessageW(edit.get_hwnd(), winapi::WM_CHAR, c as winapi::WPARAM, 0);
+        }
+    }
+}
+
+#[test]
+fn edit_keystroke_validation_test() {
+    Application::init();
+
+    let wnd = Frame::new("My Main Window", 200, 400);
+    let edt = Edit::new(&wnd, 0, 0, 100, 20, false);
+
+    edt.set_validator(Validator::integer_range(10, 99), ValidationMode::Reject);
+    type_chars(&edt, "1");
+    assert_eq!("1", edt.get_text());
+    assert!(!edt.is_valid());
+    type_chars(&edt, "23");
+    assert_eq!("12", edt.get_text());
+    assert!(edt.is_valid());
+
+    edt.set_text("");
+    edt.set_validator(Validator::ip_address(), ValidationMode::Reject);
+    type_chars(&edt, "192.168.x0.1");
+    assert_eq!("192.168.0.1", edt.get_text());
+    edt.remove_validation();
+
+    edt.set_input_mask(InputMask::new("000-000-0000"));
+    edt.set_selection(0, 0);
+    type_chars(&edt, "5551");
+    assert_eq!("555-1__-____", edt.get_text());
+
+    //Typing over a selection clears all of it
+    edt.set_selection(0, 3);
+    type_chars(&edt, "9");
+    assert_eq!("9__-1__-____", edt.get_text());
+    assert_eq!((1, 1), edt.get_selection());
+
+    type_chars(&edt, "\u{8}");
+    assert_eq!("___-1__-____", edt.get_text());
+
+    edt.set_selection(4, 5);
+    type_chars(&edt, "\u{8}");
+    assert_eq!("___-___-____", edt.get_text());
+}
+
+#[test]
+fn find_text_test() {
+    let text = "One two one\r\nTwo ones";
+    let mut options = FindOptions::new();
+
+    assert_eq!(Ok(Some((0, 3))), find_text(text, "one", 0, &options));
+    assert_eq!(Ok(Some((8, 11))), find_text(text, "one", 1, &options));
+    assert_eq!(Ok(Some((17, 20))), find_text(text, "one", 9, &options));
+    assert_eq!(Ok(Some((0, 3))), find_text(text, "one", 18, &options));
+
+    options.wrap_around = false;
+    assert_eq!(Ok(None), find_text(text, "one", 18, &options));
+
+    options.match_case = true;
+    assert_eq!(Ok(Some((8, 11))), find_text(text, "one", 0, &options));
+
+    options.match_case = false;
+    options.whole_word = true;
+    assert_eq!(Ok(None), find_text(text, "one", 9, &options));
+
+    options.whole_word = false;
+    options.direction = SearchDirection::Backward;
+    assert_eq!(Ok(Some((8, 11))), find_text(text, "one", 17, &options));
+
+    options.direction = SearchDirection::Forward;
+    options.regex = true;
+    assert_eq!(Ok(Some((4, 7))), find_text(text, "t.o", 0, &options));
+    assert!(find_text(text, "(", 0, &options).is_err());
+
+    //Positions count UTF-16 units
+    assert_eq!(Ok(Some((3, 4))), find_text("\u{1F600}éx", "x", 0, &FindOptions::new()));
+
+    assert_eq!(Ok(Some(String::from("b-a"))), replace_match("ab", "(a)(b)", "$2-$1", 0, 2, &options));
+    assert_eq!(Ok(None), replace_match("ab", "a", "x", 0, 2, &options));
+    assert_eq!(Ok(Some(String::from("b-a"))), replace_match("ab ab", "(a)(b)", "$2-$1", 3, 5, &options));
+
+    //Empty matches are neither replaced nor counted
+    assert_eq!(Ok((String::from("a-b"), 1)), replace_all_text("axb", "x*", "-", &options));
+
+    let options = FindOptions::new();
+    assert_eq!(Ok((String::from("1 two 1\r\nTwo 1s"), 3)), replace_all_text(text, "one", "1", &options));
+}
+
+#[test]
+fn find_replace_dialog_test() {
+    Application::init();
+
+    let wnd = Frame::new("My Main Window", 400, 400);
+
+    let mut options = EditOptions::new();
+    options.multi_line = true;
+    options.keep_selection = true;
+
+    let edt = Edit::with_options(&wnd, 1, 0, 0, 300, 300, &options);
+    edt.set_text("cat dog cat");
+
+    let dialog = FindReplaceDialog::new(&edt);
+    dialog.set_search_text("cat");
+    dialog.set_replace_text("bird");
+
+    assert!(dialog.find_next());
+    assert_eq!((0, 3), edt.get_selection());
+    assert!(dialog.find_next());
+    assert_eq!((8, 11), edt.get_selection());
+
+    assert!(dialog.replace());
+    assert_eq!("cat dog bird", edt.get_text());
+    assert_eq!((0, 3), edt.get_selection());
+
+    assert_eq!(1, dialog.replace_all());
+    assert_eq!("bird dog bird", edt.get_text());
+    assert!(!dialog.find_next());
+
+    //Replace All can be undone and is not cut at the text limit
+    edt.undo();
+    assert_eq!("cat dog bird", edt.get_text());
+
+    edt.set_max_length(5);
+    assert_eq!(1, dialog.replace_all());
+    assert_eq!("bird dog bird", edt.get_text());
+}
+
+#[test]
+fn rich_edit_test() {
+    Application::init();
+
+    let wnd = Frame::new("My Main Window", 400, 400);
+    let edt = RichEdit::new(&wnd, 1, 0, 0, 300, 300);
+
+    let mut error = TextStyle::new();
+    error.color = Some(Color::RED);
+    error.bold = true;
+    error.size = Some(14);
+
+    edt.append_text("INFO ");
+    edt.append_styled("ERROR", &error);
+    assert_eq!("INFO ERROR", edt.get_text());
+    assert_eq!(10, edt.get_length());
+
+    let style = edt.get_style_at(6);
+    assert_eq!(Some(Color::RED), style.color);
+    assert!(style.bold);
+    assert!(!style.italic);
+    assert_eq!(Some(14), style.size);
+
+    let style = edt.get_style_at(0);
+    assert_eq!(None, style.color);
+    assert!(!style.bold);
+    assert_eq!(None, style.size);
+
+    let rtf = edt.get_rtf();
+    assert!(rtf.starts_with("{\\rtf"));
+    assert!(rtf.contains("\\b"));
+
+    let copy = RichEdit::new(&wnd, 2, 0, 0, 300, 300);
+    assert!(copy.set_rtf(&rtf).is_ok());
+    assert_eq!("INFO ERROR", copy.get_text().trim_end());
+    assert!(copy.get_style_at(6).bold);
+
+    assert!(copy.set_rtf("plain text").is_err());
+    assert_eq!("INFO ERROR", copy.get_text().trim_end());
+
+    let empty = RichEdit::new(&wnd, 3, 0, 0, 300, 300);
+    assert!(copy.set_rtf(&empty.get_rtf()).is_ok());
+    assert_eq!("", copy.get_text().trim_end());
+}